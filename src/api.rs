@@ -1,16 +1,19 @@
-use crate::client_config::SessionToken;
+use crate::client_config::{SaveMeta, SessionToken};
 use crate::errors::LoginError::{APIError, BadCredentials, MissingSessionToken};
-use crate::errors::{FetchGamesError, LoginError};
+use crate::errors::{ExtractError, FetchGamesError, LoginError};
+use crate::handlers::crash::CrashReport;
 use crate::utils;
 use drops_messages::requests::{GetGamesRequest, GetGamesResponse};
 use log::info;
+use reqwest::header::{CONTENT_RANGE, RANGE};
 use reqwest::redirect::Policy;
-use reqwest::{Client, ClientBuilder, StatusCode};
-use std::error;
+use reqwest::{Client, ClientBuilder, Response, StatusCode};
+use std::collections::HashSet;
 use std::fs;
 use std::fs::File;
-use std::io::Cursor;
-use std::path::Path;
+use std::io;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
 use std::time::Duration;
 use zip::ZipArchive;
 
@@ -81,37 +84,357 @@ pub async fn fetch_games(
     Ok(resp)
 }
 
-pub fn unzip_file(
-    archive: &mut ZipArchive<Cursor<Vec<u8>>>,
-    output_dir: &str,
-) -> Result<(), Box<dyn error::Error>> {
-    // Iterate through the zip entries
+pub async fn fetch_release(
+    url: &str,
+    session_token: &str,
+    offset: u64,
+) -> Result<Response, reqwest::Error> {
+    let mut request = build_client().get(url).header("cookie", session_token);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    request.send().await
+}
+
+/// Fetches the SHA-256 digest published next to a release archive. Returns `None` if the
+/// server has no digest for the release, the release can't be installed then.
+pub async fn fetch_release_checksum(
+    url: &str,
+    session_token: &str,
+) -> Result<Option<String>, reqwest::Error> {
+    let resp = build_client()
+        .get(format!("{}/sha256", url))
+        .header("cookie", session_token)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await?;
+
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let digest = resp.error_for_status()?.text().await?;
+    // Accept both a bare digest and the `sha256sum` format of "<digest>  <file name>"
+    Ok(digest.split_whitespace().next().map(|x| x.to_lowercase()))
+}
+
+pub async fn send_crash_report(
+    url: &str,
+    session_token: &str,
+    report: &CrashReport,
+) -> Result<(), String> {
+    let resp = build_client()
+        .post(format!("{}/crash-reports", url))
+        .json(report)
+        .header("cookie", session_token)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // the server redirects to the login page when the session has expired
+    if resp.status().is_redirection() {
+        return Err("session expired".to_string());
+    }
+    resp.error_for_status().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Fetches the digest and time of the saves stored on the server, `None` if the game
+/// has no saves there yet.
+pub async fn fetch_save_meta(
+    url: &str,
+    session_token: &str,
+    game_name_id: &str,
+) -> Result<Option<SaveMeta>, String> {
+    let resp = build_client()
+        .get(format!("{}/saves/{}/meta", url, game_name_id))
+        .header("cookie", session_token)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match resp.status() {
+        StatusCode::NOT_FOUND => Ok(None),
+        status if status.is_redirection() => Err("session expired".to_string()),
+        _ => resp
+            .error_for_status()
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Downloads the saves of a game as a zip archive
+pub async fn fetch_saves(
+    url: &str,
+    session_token: &str,
+    game_name_id: &str,
+) -> Result<Vec<u8>, String> {
+    let resp = build_client()
+        .get(format!("{}/saves/{}", url, game_name_id))
+        .header("cookie", session_token)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if resp.status().is_redirection() {
+        return Err("session expired".to_string());
+    }
+    let bytes = resp
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(bytes.to_vec())
+}
+
+/// Uploads the saves of a game as a zip archive, replacing the ones on the server
+pub async fn upload_saves(
+    url: &str,
+    session_token: &str,
+    game_name_id: &str,
+    meta: &SaveMeta,
+    archive: Vec<u8>,
+) -> Result<(), String> {
+    let resp = build_client()
+        .post(format!("{}/saves/{}", url, game_name_id))
+        .header("cookie", session_token)
+        .header("x-save-sha256", &meta.sha256)
+        .header("x-save-modified-at", meta.modified_at.to_rfc3339())
+        .body(archive)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if resp.status().is_redirection() {
+        return Err("session expired".to_string());
+    }
+    resp.error_for_status().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Returns the first byte of a partial content response, or `None` if the server
+/// answered with the whole release.
+pub fn content_range_start(response: &Response) -> Option<u64> {
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return None;
+    }
+    let content_range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    content_range
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Returns the full size of the release from the `bytes */<size>` content range that comes
+/// with a range not satisfiable response.
+pub fn content_range_total(response: &Response) -> Option<u64> {
+    let content_range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    content_range.rsplit('/').next()?.trim().parse().ok()
+}
+
+/// Uncompressed data may be at most this many times larger than the size the server
+/// declares for the release. Game builds rarely compress better than 3:1, anything above
+/// the limit is treated as a zip bomb.
+pub const MAX_EXTRACT_RATIO: u64 = 5;
+
+/// Walks `path` component by component from the root of the output dir and returns the
+/// normalized path. Fails if the path leaves the output dir or passes through one of the
+/// `symlinks` in the archive, as those could point anywhere once created.
+fn resolve_entry_path(
+    base: &Path,
+    path: &Path,
+    symlinks: &HashSet<PathBuf>,
+) -> Result<PathBuf, &'static str> {
+    let mut resolved = base.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::CurDir => continue,
+            Component::RootDir | Component::Prefix(_) => return Err("absolute path"),
+            _ if symlinks.contains(&resolved) => return Err("path goes through a symlink"),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return Err("path escapes the install dir");
+                }
+            }
+            Component::Normal(part) => resolved.push(part),
+        }
+    }
+    Ok(resolved)
+}
+
+enum EntryKind {
+    Dir,
+    File,
+    Symlink(PathBuf),
+}
+
+struct ExtractEntry {
+    index: usize,
+    path: PathBuf,
+    kind: EntryKind,
+    mode: Option<u32>,
+}
+
+/// Checks every entry of the archive before anything is written, so a rejected archive
+/// leaves nothing behind. All rejected entries are reported at once.
+fn validate_archive(
+    archive: &mut ZipArchive<File>,
+    max_size: u64,
+) -> Result<Vec<ExtractEntry>, ExtractError> {
+    let mut rejected = vec![];
+    let mut raw_entries = vec![];
+    let mut total_size: u64 = 0;
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let outpath = Path::new(output_dir).join(file.name());
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| ExtractError::Archive(e.to_string()))?;
+        total_size = total_size.saturating_add(file.size());
+        let Some(path) = file.enclosed_name() else {
+            rejected.push(format!("{}: path escapes the install dir", file.name()));
+            continue;
+        };
+        let kind = match file.is_symlink() {
+            true => {
+                let mut target = String::new();
+                file.read_to_string(&mut target)
+                    .map_err(|e| ExtractError::Archive(e.to_string()))?;
+                EntryKind::Symlink(PathBuf::from(target))
+            }
+            false if file.is_dir() => EntryKind::Dir,
+            false => EntryKind::File,
+        };
+        raw_entries.push((i, file.name().to_string(), path, kind, file.unix_mode()));
+    }
+    if total_size > max_size {
+        return Err(ExtractError::TooLarge { limit: max_size });
+    }
+
+    let no_symlinks = HashSet::new();
+    let symlinks: HashSet<PathBuf> = raw_entries
+        .iter()
+        .filter(|(.., kind, _)| matches!(kind, EntryKind::Symlink(_)))
+        .filter_map(|(_, _, path, ..)| resolve_entry_path(Path::new(""), path, &no_symlinks).ok())
+        .collect();
+
+    let mut entries = vec![];
+    for (index, name, path, kind, mode) in raw_entries {
+        let path = match resolve_entry_path(Path::new(""), &path, &symlinks) {
+            Err(reason) => {
+                rejected.push(format!("{}: {}", name, reason));
+                continue;
+            }
+            Ok(path) if symlinks.contains(&path) && !matches!(kind, EntryKind::Symlink(_)) => {
+                rejected.push(format!("{}: path goes through a symlink", name));
+                continue;
+            }
+            Ok(path) => path,
+        };
+        if let EntryKind::Symlink(target) = &kind {
+            let link_dir = path.parent().unwrap_or(Path::new(""));
+            if let Err(reason) = resolve_entry_path(link_dir, target, &symlinks) {
+                rejected.push(format!(
+                    "{}: symlink to {}, {}",
+                    name,
+                    target.display(),
+                    reason
+                ));
+                continue;
+            }
+        }
+        entries.push(ExtractEntry {
+            index,
+            path,
+            kind,
+            mode,
+        });
+    }
+
+    match rejected.is_empty() {
+        true => Ok(entries),
+        false => Err(ExtractError::RejectedEntries(rejected)),
+    }
+}
+
+/// Removes whatever is at `path` if it is a symlink, so writing to it can't follow a link
+/// left behind by an earlier install.
+fn remove_existing_symlink(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+pub fn unzip_file(
+    archive: &mut ZipArchive<File>,
+    output_dir: &Path,
+    max_size: u64,
+) -> Result<(), ExtractError> {
+    let entries = validate_archive(archive, max_size)?;
+    let mut remaining = max_size;
+
+    // Symlinks are created last, so no file is ever written through one
+    let (symlinks, entries): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|x| matches!(x.kind, EntryKind::Symlink(_)));
+
+    for entry in entries.iter().chain(symlinks.iter()) {
+        let mut file = archive
+            .by_index(entry.index)
+            .map_err(|e| ExtractError::Archive(e.to_string()))?;
+        let outpath = output_dir.join(&entry.path);
 
         info!("Extracting file: {}", outpath.display());
 
-        // Check if the file is a directory or a file
-        if file.name().ends_with('/') {
-            fs::create_dir_all(&outpath)?;
-        } else {
-            // Create the directory if it doesn't exist
-            if let Some(parent) = outpath.parent() {
-                if !parent.exists() {
-                    fs::create_dir_all(parent)?;
+        if let Some(parent) = outpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        remove_existing_symlink(&outpath)?;
+        match &entry.kind {
+            EntryKind::Dir => fs::create_dir_all(&outpath)?,
+            EntryKind::File => {
+                let mut outfile = File::create(&outpath)?;
+                // The declared sizes were checked already, but they can lie
+                let written = io::copy(&mut (&mut file).take(remaining + 1), &mut outfile)?;
+                if written > remaining {
+                    return Err(ExtractError::TooLarge { limit: max_size });
+                }
+                remaining -= written;
+            }
+            #[cfg(unix)]
+            EntryKind::Symlink(target) => {
+                if outpath.exists() {
+                    fs::remove_file(&outpath)?;
                 }
+                std::os::unix::fs::symlink(target, &outpath)?;
+            }
+            #[cfg(not(unix))]
+            EntryKind::Symlink(target) => {
+                log::warn!(
+                    "skipping symlink {} -> {}, not supported on this platform",
+                    outpath.display(),
+                    target.display()
+                );
             }
-            // Extract the file
-            let mut outfile = File::create(&outpath)?;
-            std::io::copy(&mut file, &mut outfile)?;
         }
 
-        // If the file has a Unix mode (like permissions), set it
+        // Only keep the permission bits, never setuid/setgid or write access for others.
+        // The owner always keeps access so the release can be removed again.
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            if let Some(mode) = file.unix_mode() {
+            let mode = match (&entry.kind, entry.mode) {
+                (EntryKind::Symlink(_), _) | (_, None) => None,
+                (EntryKind::Dir, Some(mode)) => Some(mode & 0o755 | 0o700),
+                (EntryKind::File, Some(mode)) => Some(mode & 0o755 | 0o600),
+            };
+            if let Some(mode) = mode {
                 fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
             }
         }
@@ -136,3 +459,127 @@ pub async fn can_reach_host(url: String) -> Result<(), String> {
         Err(e) => Err(e.to_string()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    enum Entry<'a> {
+        File(&'a str, &'a [u8]),
+        Symlink(&'a str, &'a str),
+    }
+
+    fn archive(entries: &[Entry]) -> ZipArchive<File> {
+        let mut writer = ZipWriter::new(tempfile::tempfile().unwrap());
+        let options = SimpleFileOptions::default();
+        for entry in entries {
+            match entry {
+                Entry::File(name, data) => {
+                    writer.start_file(*name, options).unwrap();
+                    writer.write_all(data).unwrap();
+                }
+                Entry::Symlink(name, target) => {
+                    writer.add_symlink(*name, *target, options).unwrap()
+                }
+            }
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    fn rejected(entries: &[Entry]) -> Vec<String> {
+        match validate_archive(&mut archive(entries), 1024) {
+            Err(ExtractError::RejectedEntries(rejected)) => rejected,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("archive was accepted"),
+        }
+    }
+
+    #[test]
+    fn resolves_entry_paths_inside_the_output_dir() {
+        let symlinks = HashSet::new();
+        let resolve = |path| resolve_entry_path(Path::new(""), Path::new(path), &symlinks);
+        assert_eq!(resolve("bin/game"), Ok(PathBuf::from("bin/game")));
+        assert_eq!(resolve("./bin/../game"), Ok(PathBuf::from("game")));
+        assert_eq!(resolve("../game"), Err("path escapes the install dir"));
+        assert_eq!(
+            resolve("bin/../../game"),
+            Err("path escapes the install dir")
+        );
+        assert_eq!(resolve("/etc/passwd"), Err("absolute path"));
+    }
+
+    #[test]
+    fn rejects_entry_paths_through_symlinks() {
+        let symlinks = HashSet::from([PathBuf::from("data")]);
+        let resolve = |path| resolve_entry_path(Path::new(""), Path::new(path), &symlinks);
+        assert_eq!(resolve("data/save"), Err("path goes through a symlink"));
+        assert_eq!(resolve("data"), Ok(PathBuf::from("data")));
+        assert_eq!(resolve("other/save"), Ok(PathBuf::from("other/save")));
+    }
+
+    #[test]
+    fn accepts_safe_archive() {
+        let entries = validate_archive(
+            &mut archive(&[
+                Entry::File("game", b"binary"),
+                Entry::File("data/level", b"level"),
+                Entry::Symlink("current", "data/level"),
+            ]),
+            1024,
+        )
+        .unwrap();
+        let paths: Vec<_> = entries.iter().map(|x| x.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("game"),
+                PathBuf::from("data/level"),
+                PathBuf::from("current")
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_escaping_and_absolute_entries() {
+        let rejected = rejected(&[
+            Entry::File("game", b"binary"),
+            Entry::File("../outside", b"evil"),
+            Entry::File("/etc/passwd", b"evil"),
+        ]);
+        assert_eq!(rejected.len(), 2);
+        assert!(rejected[0].starts_with("../outside"));
+        assert!(rejected[1].starts_with("/etc/passwd"));
+    }
+
+    #[test]
+    fn rejects_symlink_out_of_the_output_dir() {
+        let rejected = rejected(&[
+            Entry::Symlink("up", "../.."),
+            Entry::Symlink("root", "/etc"),
+        ]);
+        assert_eq!(rejected.len(), 2);
+        assert!(rejected[0].contains("path escapes the install dir"));
+        assert!(rejected[1].contains("absolute path"));
+    }
+
+    #[test]
+    fn rejects_entry_through_symlink() {
+        let rejected = rejected(&[
+            Entry::Symlink("data", "saves"),
+            Entry::File("data/evil", b"evil"),
+        ]);
+        assert_eq!(rejected, vec!["data/evil: path goes through a symlink"]);
+    }
+
+    #[test]
+    fn rejects_archive_above_size_cap() {
+        let result = validate_archive(&mut archive(&[Entry::File("game", &[0; 2048])]), 1024);
+        assert!(matches!(
+            result,
+            Err(ExtractError::TooLarge { limit: 1024 })
+        ));
+    }
+}
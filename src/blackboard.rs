@@ -1,6 +1,5 @@
 use crate::client_config::{ClientConfig, Game, Release};
 use crate::{utils, Screen};
-use std::process::Command;
 
 #[derive(Default, Clone)]
 pub struct Blackboard {
@@ -9,7 +8,65 @@ pub struct Blackboard {
     pub selected_game: Option<Game>,
     pub selected_channel: Option<String>,
     pub selected_version: Option<String>,
-    pub is_playing: bool,
+    /// Shown in the top bar, e.g. how the last game session ended
+    pub status_message: Option<String>,
+    pub activity: Activity,
+}
+
+/// What runs for the active account. Games, downloads and library moves belong to their
+/// handlers, the app copies their state here after every message.
+#[derive(Default, Clone)]
+pub struct Activity {
+    /// [`utils::release_id`] of the releases that are starting or running
+    pub playing: Vec<String>,
+    /// [`utils::release_id`] of the releases that are queued or being downloaded
+    pub downloading: Vec<String>,
+    pub moving_library: bool,
+}
+
+impl Blackboard {
+    /// The active account can't be switched, edited or removed while something runs for it
+    pub(crate) fn active_account_busy(&self) -> bool {
+        !self.activity.playing.is_empty()
+            || !self.activity.downloading.is_empty()
+            || self.activity.moving_library
+    }
+
+    /// The files of the release are in use by a game or a download
+    pub(crate) fn release_busy(
+        &self,
+        game_name_id: &str,
+        channel_name: &str,
+        version: &str,
+    ) -> bool {
+        let id = utils::release_id(game_name_id, channel_name, version);
+        self.activity.playing.contains(&id) || self.activity.downloading.contains(&id)
+    }
+
+    /// Any release of the game is in use by a game or a download
+    pub(crate) fn game_busy(&self, game_name_id: &str) -> bool {
+        let prefix = format!("{}/", game_name_id);
+        self.activity
+            .playing
+            .iter()
+            .chain(&self.activity.downloading)
+            .any(|x| x.starts_with(&prefix))
+    }
+
+    /// Releases of the channel that fall outside the retention policy of the game, except
+    /// those that are running or being downloaded
+    pub(crate) fn releases_to_prune(
+        &self,
+        game_name_id: &str,
+        channel_name: &str,
+        installed_version: &str,
+    ) -> Vec<Release> {
+        self.config
+            .get_releases_to_prune(game_name_id, channel_name, installed_version)
+            .into_iter()
+            .filter(|x| !self.release_busy(game_name_id, &x.channel_name, &x.version))
+            .collect()
+    }
 }
 
 impl Blackboard {
@@ -43,26 +100,81 @@ impl Blackboard {
             .clone();
         self.selected_game = Some(updated_game);
     }
+}
 
-    pub fn run_release(&mut self, game: &Game, release: &Release) {
-        let executable_dir = utils::get_exe_path(
-            &self.config.get_games_dir(),
-            &game.name_id,
-            &release.channel_name,
-            &release.version,
-        );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_config::{DropsAccountConfig, ReleaseState};
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
 
-        let executable_path = executable_dir.join(&release.executable_path);
-        let mut child = Command::new(&executable_path)
-            .current_dir(&executable_dir)
-            .envs(std::env::vars())
-            .spawn()
-            .expect(&format!(
-                "Failed to run the binary at: {:?}",
-                executable_path
-            ));
+    fn release(version: &str, day: u32) -> Release {
+        Release {
+            channel_name: "stable".to_string(),
+            version: version.to_string(),
+            description: String::new(),
+            state: ReleaseState::Installed,
+            release_date: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            executable_path: "game".to_string(),
+            size_bytes: 0,
+            pinned: false,
+        }
+    }
+
+    fn blackboard_with_releases(releases: Vec<Release>) -> Blackboard {
+        let account = DropsAccountConfig {
+            id: Uuid::new_v4(),
+            name: None,
+            games_dir: "games".to_string(),
+            url: String::new(),
+            username: String::new(),
+            session_token: Default::default(),
+            games: vec![Game {
+                name_id: "space".to_string(),
+                releases,
+                keep_installed: Some(1),
+                ..Default::default()
+            }],
+        };
+        let mut blackboard = Blackboard::default();
+        blackboard.config.active_account = account.id;
+        blackboard.config.accounts.push(account);
+        blackboard
+    }
 
-        let _ = child.wait();
-        self.is_playing = true;
+    fn versions(releases: Vec<Release>) -> Vec<String> {
+        releases.into_iter().map(|x| x.version).collect()
+    }
+
+    #[test]
+    fn prunes_releases_beyond_retention() {
+        let blackboard = blackboard_with_releases(vec![
+            release("1.0", 1),
+            release("1.1", 2),
+            release("1.2", 3),
+        ]);
+        assert_eq!(
+            versions(blackboard.releases_to_prune("space", "stable", "1.2")),
+            vec!["1.1", "1.0"]
+        );
+    }
+
+    #[test]
+    fn running_and_downloading_releases_survive_pruning() {
+        let mut blackboard = blackboard_with_releases(vec![
+            release("1.0", 1),
+            release("1.1", 2),
+            release("1.2", 3),
+        ]);
+        blackboard.activity.playing = vec![utils::release_id("space", "stable", "1.0")];
+        assert_eq!(
+            versions(blackboard.releases_to_prune("space", "stable", "1.2")),
+            vec!["1.1"]
+        );
+        blackboard.activity.downloading = vec![utils::release_id("space", "stable", "1.1")];
+        assert!(blackboard
+            .releases_to_prune("space", "stable", "1.2")
+            .is_empty());
     }
 }
@@ -1,17 +1,23 @@
+use crate::config_migrations;
+use crate::config_migrations::{schema_version, CURRENT_SCHEMA_VERSION};
 use crate::errors::ConfigError;
 use crate::utils;
 use anyhow::{anyhow, Error};
 use chrono::{DateTime, Utc};
 use directories::ProjectDirs;
 use drops_messages::requests::{GameInfoResponse, GetGamesResponse, ReleaseInfoResponse};
+use log::{error, info};
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
+use serde_json::{from_str, Value};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 pub fn get_config_dir() -> PathBuf {
@@ -25,6 +31,90 @@ pub fn get_config_file_path() -> PathBuf {
     get_config_dir().join("config.json")
 }
 
+pub fn get_config_backups_dir() -> PathBuf {
+    get_config_dir().join("backups")
+}
+
+/// Backups of the config file, newest first. Names are timestamps, so they sort by age.
+fn list_config_backups() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(get_config_backups_dir()) else {
+        return vec![];
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|x| x == "json"))
+        .collect();
+    backups.sort_by(|x, y| y.cmp(x));
+    backups
+}
+
+/// Copies the current config into the backup ring. The config is saved on most changes,
+/// so at most one backup is made per [`CONFIG_BACKUP_INTERVAL`] unless `force` is set.
+fn backup_config(force: bool) -> io::Result<()> {
+    let path = get_config_file_path();
+    if !path.exists() {
+        return Ok(());
+    }
+    let backups = list_config_backups();
+    let newest_age = backups
+        .first()
+        .and_then(|x| x.metadata().ok()?.modified().ok()?.elapsed().ok());
+    if !force && newest_age.is_some_and(|x| x < CONFIG_BACKUP_INTERVAL) {
+        return Ok(());
+    }
+    fs::create_dir_all(get_config_backups_dir())?;
+    // a forced backup and a regular one can happen within the same millisecond, the
+    // counter keeps them apart and sorted
+    let timestamp = Utc::now().format("%Y%m%d-%H%M%S%.3f");
+    let backup_path = (0..)
+        .map(|x| get_config_backups_dir().join(format!("config-{}-{:03}.json", timestamp, x)))
+        .find(|x| !x.exists())
+        .unwrap();
+    info!("backing up config to {}", backup_path.display());
+    fs::copy(&path, backup_path)?;
+    // the newest backup that parses is kept even past the limit, it is the one offered
+    // when the config breaks
+    let newest_valid = newest_valid_backup().map(|x| x.path);
+    for old_backup in backups.iter().skip(MAX_CONFIG_BACKUPS - 1) {
+        if Some(old_backup) != newest_valid.as_ref() {
+            fs::remove_file(old_backup)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigBackup {
+    pub path: PathBuf,
+    pub modified: DateTime<Utc>,
+}
+
+/// The newest backup that still parses, offered when the config itself can't be loaded
+pub fn newest_valid_backup() -> Option<ConfigBackup> {
+    list_config_backups().into_iter().find_map(|path| {
+        let contents = fs::read_to_string(&path).ok()?;
+        if let Err(e) = ClientConfig::parse(&contents) {
+            info!("skipping invalid config backup {}: {}", path.display(), e);
+            return None;
+        }
+        let modified = path.metadata().ok()?.modified().ok()?.into();
+        Some(ConfigBackup { path, modified })
+    })
+}
+
+/// Moves a config that can't be loaded out of the way, so saving a new one doesn't
+/// overwrite it. Returns where the file was moved to.
+pub fn move_broken_config() -> io::Result<PathBuf> {
+    let broken_path = get_config_dir().join(format!(
+        "config-broken-{}.json",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::rename(get_config_file_path(), &broken_path)?;
+    info!("moved broken config to {}", broken_path.display());
+    Ok(broken_path)
+}
+
 pub fn ensure_path() {
     let path = get_config_dir();
 
@@ -54,15 +144,44 @@ impl Display for SessionToken {
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+const MAX_CONFIG_BACKUPS: usize = 5;
+const CONFIG_BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+fn default_max_parallel_downloads() -> usize {
+    2
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct ClientConfig {
+    /// Set by [`config_migrations::migrate`] when the config is loaded
+    pub schema_version: u32,
     pub active_account: Uuid,
     pub accounts: Vec<DropsAccountConfig>,
     pub is_active: bool,
+    #[serde(default = "default_max_parallel_downloads")]
+    pub max_parallel_downloads: usize,
+    /// Wrapper command games are launched through, unless they set their own
+    #[serde(default)]
+    pub launch_prefix: Option<String>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            active_account: Default::default(),
+            accounts: vec![],
+            is_active: false,
+            max_parallel_downloads: default_max_parallel_downloads(),
+            launch_prefix: None,
+        }
+    }
 }
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct DropsAccountConfig {
     pub id: Uuid,
+    /// Chosen by the user, shown instead of `user@server` when set
+    pub name: Option<String>,
     pub games_dir: String,
     pub url: String,
     pub username: String,
@@ -80,6 +199,81 @@ pub struct Game {
     pub selected_channel: Option<String>,
     pub releases: Vec<Release>,
     pub app_link: Option<PathBuf>,
+    /// Installed releases to keep per channel, older ones are removed after an install
+    #[serde(default)]
+    pub keep_installed: Option<usize>,
+    #[serde(default)]
+    pub sessions: Vec<PlaySession>,
+    #[serde(default)]
+    pub launch_options: LaunchOptions,
+    /// Files and directories synced with the drops server, relative paths are resolved
+    /// from the release directory
+    #[serde(default)]
+    pub save_paths: Vec<String>,
+    /// Saves as of the last sync with the server, used to tell which side changed since
+    #[serde(default)]
+    pub synced_saves: Option<SaveMeta>,
+    /// Glob patterns of files copied from the previous version when a release is installed
+    #[serde(default)]
+    pub carry_over: Vec<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub struct SaveMeta {
+    /// Digest of the save file names and contents
+    pub sha256: String,
+    pub modified_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Default, Debug, Clone, PartialEq)]
+pub struct LaunchOptions {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// Relative paths are resolved from the release directory
+    pub working_dir: Option<String>,
+    /// Overrides [`ClientConfig::launch_prefix`] for this game
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct PlaySession {
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: u64,
+    pub version: String,
+    pub channel_name: String,
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub log_file: Option<PathBuf>,
+}
+
+impl Game {
+    pub fn total_playtime_secs(&self) -> u64 {
+        self.sessions.iter().map(|x| x.duration_secs).sum()
+    }
+
+    pub fn last_played(&self) -> Option<DateTime<Utc>> {
+        self.sessions.iter().map(|x| x.started_at).max()
+    }
+
+    /// Playtime per (channel, version), most played first
+    pub fn playtime_by_release(&self) -> Vec<(String, String, u64)> {
+        let mut by_release: HashMap<(String, String), u64> = HashMap::new();
+        for session in self.sessions.iter() {
+            *by_release
+                .entry((
+                    session.channel_name.to_string(),
+                    session.version.to_string(),
+                ))
+                .or_default() += session.duration_secs;
+        }
+        let mut by_release: Vec<(String, String, u64)> = by_release
+            .into_iter()
+            .map(|((channel, version), secs)| (channel, version, secs))
+            .collect();
+        by_release.sort_by_key(|(.., secs)| std::cmp::Reverse(*secs));
+        by_release
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize, PartialEq, Debug, Clone)]
@@ -97,11 +291,62 @@ pub struct Release {
     pub release_date: DateTime<Utc>,
     pub executable_path: String,
     pub size_bytes: u64,
+    /// Pinned releases are never removed by the retention policy
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 impl ClientConfig {
-    pub(crate) fn set_active_account_by_url(&mut self, url: String) {
-        self.active_account = self.accounts.iter().find(|x| x.url == url).unwrap().id;
+    pub(crate) fn set_active_account(&mut self, id: Uuid) {
+        if self.accounts.iter().any(|x| x.id == id) {
+            self.active_account = id;
+        }
+    }
+
+    pub(crate) fn get_account(&self, id: Uuid) -> Option<&DropsAccountConfig> {
+        self.accounts.iter().find(|x| x.id == id)
+    }
+
+    pub(crate) fn update_account_and_save(
+        &mut self,
+        id: Uuid,
+        update: impl FnOnce(&mut DropsAccountConfig),
+    ) {
+        if let Some(account) = self.accounts.iter_mut().find(|x| x.id == id) {
+            update(account);
+        }
+        self.save().unwrap();
+    }
+
+    /// Removes an account, another one becomes active if it was the active one.
+    /// Without accounts left the config is no longer active and the wizard shows up.
+    pub(crate) fn remove_account_and_save(&mut self, id: Uuid) {
+        self.accounts.retain(|x| x.id != id);
+        if self.active_account == id {
+            self.active_account = self.accounts.first().map(|x| x.id).unwrap_or_default();
+        }
+        self.is_active = !self.accounts.is_empty();
+        self.save().unwrap();
+    }
+
+    /// Games of the other accounts that use the same games dir as the account
+    pub(crate) fn shared_games(&self, account: &DropsAccountConfig) -> Vec<String> {
+        self.accounts
+            .iter()
+            .filter(|x| x.id != account.id && x.games_dir == account.games_dir)
+            .flat_map(|x| x.games.iter().map(|x| x.name_id.to_string()))
+            .collect()
+    }
+
+    /// Another account on the same server as the active one, logged in as `username`
+    pub(crate) fn find_account_on_active_server(
+        &self,
+        username: &str,
+    ) -> Option<&DropsAccountConfig> {
+        let active = self.get_active_account()?;
+        self.accounts
+            .iter()
+            .find(|x| x.id != active.id && x.url == active.url && x.username == username)
     }
     pub fn get_active_account(&self) -> Option<DropsAccountConfig> {
         self.accounts
@@ -163,6 +408,93 @@ impl ClientConfig {
         Ok(())
     }
 
+    fn update_game_and_save(&mut self, game_name_id: &str, update: impl FnOnce(&mut Game)) {
+        let mut account = self.get_active_account().unwrap();
+        if let Some(game) = account.games.iter_mut().find(|x| x.name_id == game_name_id) {
+            update(game);
+        }
+        self.patch_account_and_save(account);
+    }
+
+    pub(crate) fn set_app_link(&mut self, game_name_id: &str, app_link: Option<PathBuf>) {
+        self.update_game_and_save(game_name_id, |game| game.app_link = app_link);
+    }
+
+    pub(crate) fn add_session(&mut self, game_name_id: &str, session: PlaySession) {
+        self.update_game_and_save(game_name_id, |game| game.sessions.push(session));
+    }
+
+    pub(crate) fn set_launch_options(&mut self, game_name_id: &str, options: LaunchOptions) {
+        self.update_game_and_save(game_name_id, |game| game.launch_options = options);
+    }
+
+    pub(crate) fn set_save_paths(&mut self, game_name_id: &str, save_paths: Vec<String>) {
+        self.update_game_and_save(game_name_id, |game| game.save_paths = save_paths);
+    }
+
+    pub(crate) fn set_carry_over(&mut self, game_name_id: &str, carry_over: Vec<String>) {
+        self.update_game_and_save(game_name_id, |game| game.carry_over = carry_over);
+    }
+
+    pub(crate) fn set_synced_saves(&mut self, game_name_id: &str, synced: Option<SaveMeta>) {
+        self.update_game_and_save(game_name_id, |game| game.synced_saves = synced);
+    }
+
+    /// Adds a release the server doesn't list, e.g. one found in the library
+    pub(crate) fn add_release(&mut self, game_name_id: &str, release: Release) {
+        self.update_game_and_save(game_name_id, |game| game.releases.push(release));
+    }
+
+    pub(crate) fn set_keep_installed(&mut self, game_name_id: &str, keep: Option<usize>) {
+        self.update_game_and_save(game_name_id, |game| game.keep_installed = keep);
+    }
+
+    pub(crate) fn set_pinned(
+        &mut self,
+        game_name_id: &str,
+        version: &str,
+        channel_name: &str,
+        pinned: bool,
+    ) {
+        self.update_game_and_save(game_name_id, |game| {
+            game.releases
+                .iter_mut()
+                .filter(|x| x.version == version && x.channel_name == channel_name)
+                .for_each(|x| x.pinned = pinned)
+        });
+    }
+
+    /// Installed releases of the channel that fall outside the retention policy of the game.
+    /// Pinned releases and `installed_version` are always kept.
+    pub(crate) fn get_releases_to_prune(
+        &self,
+        game_name_id: &str,
+        channel_name: &str,
+        installed_version: &str,
+    ) -> Vec<Release> {
+        let account = self.get_active_account().unwrap();
+        let Some(game) = account.games.iter().find(|x| x.name_id == game_name_id) else {
+            return vec![];
+        };
+        let Some(keep) = game.keep_installed else {
+            return vec![];
+        };
+
+        let mut installed: Vec<&Release> = game
+            .releases
+            .iter()
+            .filter(|x| x.channel_name == channel_name)
+            .filter(|x| x.state == ReleaseState::Installed && !x.pinned)
+            .collect();
+        installed.sort_by_key(|x| std::cmp::Reverse(x.release_date));
+        installed
+            .into_iter()
+            .skip(keep.max(1))
+            .filter(|x| x.version != installed_version)
+            .cloned()
+            .collect()
+    }
+
     fn patch_account_and_save(&mut self, account: DropsAccountConfig) {
         match self.accounts.iter_mut().find(|x| x.id == account.id) {
             None => {
@@ -174,12 +506,25 @@ impl ClientConfig {
         }
         self.save().unwrap();
     }
+    /// Writes the config to a temporary file first and renames it into place, so the
+    /// client being killed mid-write can't leave a truncated config behind.
     pub(crate) fn save(&self) -> Result<(), Error> {
         ensure_path();
         let as_str = serde_json::to_string(self)?;
 
-        let mut file = File::create(get_config_file_path())?;
+        if let Err(e) = backup_config(false) {
+            error!("failed to back up config: {}", e);
+        }
+
+        let path = get_config_file_path();
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path)?;
         file.write_all(as_str.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+        // the rename itself only lasts once the directory entry is on disk
+        #[cfg(unix)]
+        File::open(get_config_dir())?.sync_all()?;
 
         Ok(())
     }
@@ -201,15 +546,64 @@ impl ClientConfig {
             .map(Arc::new)
             .map_err(|error| ConfigError::IoError(error.kind()))?;
 
-        let config: ClientConfig = match from_str(&contents) {
-            Ok(c) => c,
-            Err(_) => return Err(ConfigError::DialogClosed),
-        };
+        let json: Value = from_str(&contents)?;
+        if schema_version(&json) < CURRENT_SCHEMA_VERSION {
+            // keep the old file around in case the migrated config turns out to be wrong
+            if let Err(e) = backup_config(true) {
+                error!("failed to back up config before migrating: {}", e);
+            }
+        }
+
+        ClientConfig::from_json(&contents, json)
+    }
+
+    fn parse(contents: &str) -> Result<ClientConfig, ConfigError> {
+        let json: Value = from_str(contents)?;
+        ClientConfig::from_json(contents, json)
+    }
+
+    /// Migrates config JSON from older schema versions before deserializing it. Current
+    /// configs are deserialized from the text, so errors point at a line in the file.
+    fn from_json(contents: &str, json: Value) -> Result<ClientConfig, ConfigError> {
+        if schema_version(&json) == CURRENT_SCHEMA_VERSION {
+            return Ok(from_str(contents)?);
+        }
+        let json = config_migrations::migrate(json).map_err(ConfigError::Migration)?;
+        Ok(serde_json::from_value(json)?)
+    }
+
+    /// Loads a backup and saves it as the config
+    pub async fn restore_backup(backup: PathBuf) -> Result<ClientConfig, ConfigError> {
+        let contents = tokio::fs::read_to_string(&backup)
+            .await
+            .map_err(|error| ConfigError::IoError(error.kind()))?;
+
+        let config = ClientConfig::parse(&contents)?;
+        info!("restoring config from {}", backup.display());
+        config
+            .save()
+            .map_err(|_| ConfigError::IoError(io::ErrorKind::Other))?;
 
         Ok(config)
     }
 }
 impl DropsAccountConfig {
+    /// The name given by the user or `user@server`, how accounts are told apart in the UI
+    pub fn display_name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.to_string();
+        }
+        let server = self
+            .url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+        match self.username.is_empty() {
+            true => format!("new account @ {}", server),
+            false => format!("{}@{}", self.username, server),
+        }
+    }
+
     fn create_new_release(r: &ReleaseInfoResponse) -> Release {
         Release {
             channel_name: r.channel.to_string(),
@@ -219,6 +613,7 @@ impl DropsAccountConfig {
             release_date: r.release_date,
             executable_path: r.executable_path.to_string(),
             size_bytes: r.size_bytes,
+            pinned: false,
         }
     }
 
@@ -230,7 +625,7 @@ impl DropsAccountConfig {
             .collect();
 
         let selected_channel = match game_info.default_channel {
-            None if releases.first().is_some() => {
+            None if !releases.is_empty() => {
                 Some(releases.first().unwrap().channel_name.to_string())
             }
             Some(channel) => Some(channel),
@@ -245,6 +640,12 @@ impl DropsAccountConfig {
             orphaned: false,
             selected_channel,
             app_link: None,
+            keep_installed: None,
+            sessions: vec![],
+            launch_options: Default::default(),
+            save_paths: vec![],
+            synced_saves: None,
+            carry_over: vec![],
         };
 
         self.games.push(stored_game);
@@ -264,6 +665,12 @@ impl DropsAccountConfig {
             selected_channel: existing_game.selected_channel,
             releases: vec![],
             app_link: existing_game.app_link,
+            keep_installed: existing_game.keep_installed,
+            sessions: existing_game.sessions,
+            launch_options: existing_game.launch_options,
+            save_paths: existing_game.save_paths,
+            synced_saves: existing_game.synced_saves,
+            carry_over: existing_game.carry_over,
         };
 
         let new: Vec<_> = game_info
@@ -281,24 +688,12 @@ impl DropsAccountConfig {
 
         patched_game.releases.extend(existing_game.releases);
 
-        for release in patched_game.releases.iter_mut() {
-            let path = utils::get_exe_path(
-                &self.games_dir,
-                &patched_game.name_id,
-                &release.channel_name,
-                &release.version,
-            )
-            .join(&release.executable_path);
-            release.state = match path.exists() {
-                true => ReleaseState::Installed,
-                false => ReleaseState::NotInstalled,
-            };
-        }
+        Self::refresh_install_state(&self.games_dir, &mut patched_game);
 
         match self
             .games
             .iter_mut()
-            .find(|x| &x.name_id == &patched_game.name_id)
+            .find(|x| x.name_id == patched_game.name_id)
         {
             None => Err(anyhow!(
                 "Failed to find patch game with name_id: {}",
@@ -311,6 +706,42 @@ impl DropsAccountConfig {
         }
     }
 
+    fn refresh_install_state(games_dir: &str, game: &mut Game) {
+        for release in game.releases.iter_mut() {
+            let path = utils::get_exe_path(
+                games_dir,
+                &game.name_id,
+                &release.channel_name,
+                &release.version,
+            )
+            .join(&release.executable_path);
+            release.state = match path.exists() {
+                true => ReleaseState::Installed,
+                false => ReleaseState::NotInstalled,
+            };
+        }
+    }
+
+    /// Marks releases installed or not by what is in the games dir
+    pub(crate) fn refresh_install_states(&mut self) {
+        for game in self.games.iter_mut() {
+            Self::refresh_install_state(&self.games_dir, game);
+        }
+    }
+
+    /// Games with at least one installed release
+    pub(crate) fn installed_games(&self) -> Vec<String> {
+        self.games
+            .iter()
+            .filter(|x| {
+                x.releases
+                    .iter()
+                    .any(|r| r.state == ReleaseState::Installed)
+            })
+            .map(|x| x.name_id.to_string())
+            .collect()
+    }
+
     pub fn handle_game_response(
         &mut self,
         game_info_response: GetGamesResponse,
@@ -330,15 +761,14 @@ impl DropsAccountConfig {
         }
 
         let orphaned_games: Vec<String> = existing_game_map
-            .into_iter()
-            .map(|(_, game)| game.name_id)
+            .into_values()
+            .map(|game| game.name_id)
             .collect();
 
-        if orphaned_games.len() > 0 {
+        if !orphaned_games.is_empty() {
             self.games
                 .iter_mut()
                 .filter(|x| orphaned_games.contains(&x.name_id.to_string()))
-                .into_iter()
                 .for_each(|x| x.orphaned = true);
         }
 
@@ -352,7 +782,7 @@ impl DropsAccountConfig {
         channel_name: &str,
         state: ReleaseState,
     ) -> Result<(), Error> {
-        match self.games.iter_mut().find(|x| &x.name_id == &game_name_id) {
+        match self.games.iter_mut().find(|x| x.name_id == game_name_id) {
             None => Err(anyhow!(
                 "Failed to find game with name_id: {}",
                 &game_name_id
@@ -360,7 +790,7 @@ impl DropsAccountConfig {
             Some(game) => match game
                 .releases
                 .iter_mut()
-                .find(|y| &y.version == &version && &y.channel_name == channel_name)
+                .find(|y| y.version == version && y.channel_name == channel_name)
             {
                 None => Err(anyhow!(
                     "Failed to find release {} {}",
@@ -0,0 +1,178 @@
+use log::info;
+use serde_json::{json, Value};
+
+/// Version of the config structs, bump it together with a new entry in [`MIGRATIONS`]
+/// whenever a field is added, renamed or removed.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// The migration at index `n` upgrades a config from schema version `n` to `n + 1`
+const MIGRATIONS: [Migration; CURRENT_SCHEMA_VERSION as usize] =
+    [add_unversioned_fields, add_account_names];
+
+/// Configs written before schema versioning have no version and count as version 0
+pub fn schema_version(config: &Value) -> u32 {
+    config
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32
+}
+
+/// Upgrades config JSON of any older schema version to the current one, step by step
+pub fn migrate(mut config: Value) -> Result<Value, String> {
+    let version = schema_version(&config);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "config has schema version {}, this client only knows up to {}",
+            version, CURRENT_SCHEMA_VERSION
+        ));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        info!("migrating config from schema {} to {}", from, from + 1);
+        migration(&mut config).map_err(|e| format!("migration to schema {}: {}", from + 1, e))?;
+        config
+            .as_object_mut()
+            .ok_or("config is not an object")?
+            .insert("schema_version".to_string(), json!(from + 1));
+    }
+    Ok(config)
+}
+
+fn insert_missing(object: &mut Value, defaults: Value) -> Result<(), String> {
+    let (Some(object), Value::Object(defaults)) = (object.as_object_mut(), defaults) else {
+        return Err("expected an object".to_string());
+    };
+    for (key, value) in defaults {
+        object.entry(key).or_insert(value);
+    }
+    Ok(())
+}
+
+fn items_mut<'a>(object: &'a mut Value, key: &str) -> Result<&'a mut Vec<Value>, String> {
+    object
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .ok_or(format!("missing list {}", key))
+}
+
+/// Version 0 to 1: writes out the fields that were added with serde defaults before the
+/// config had a schema version.
+fn add_unversioned_fields(config: &mut Value) -> Result<(), String> {
+    insert_missing(
+        config,
+        json!({"max_parallel_downloads": 2, "launch_prefix": null}),
+    )?;
+    for account in items_mut(config, "accounts")? {
+        for game in items_mut(account, "games")? {
+            insert_missing(
+                game,
+                json!({
+                    "keep_installed": null,
+                    "sessions": [],
+                    "launch_options": {"args": [], "env": [], "working_dir": null},
+                    "save_paths": [],
+                    "synced_saves": null,
+                    "carry_over": [],
+                }),
+            )?;
+            insert_missing(&mut game["launch_options"], json!({"prefix": null}))?;
+            for session in items_mut(game, "sessions")? {
+                insert_missing(session, json!({"log_file": null}))?;
+            }
+            for release in items_mut(game, "releases")? {
+                insert_missing(release, json!({"pinned": false}))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 1 to 2: accounts can be given a name
+fn add_account_names(config: &mut Value) -> Result<(), String> {
+    for account in items_mut(config, "accounts")? {
+        insert_missing(account, json!({"name": null}))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_config::{ClientConfig, ReleaseState};
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> Value {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/config")
+            .join(name);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    fn load(name: &str) -> ClientConfig {
+        serde_json::from_value(migrate(fixture(name)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn unversioned_configs_are_version_0() {
+        assert_eq!(schema_version(&fixture("v0_client_0_1_5.json")), 0);
+    }
+
+    #[test]
+    fn migrates_0_1_5_config() {
+        let config = load("v0_client_0_1_5.json");
+        assert_eq!(config.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(config.max_parallel_downloads, 2);
+        let account = &config.accounts[0];
+        assert_eq!(account.username, "tester");
+        let game = &account.games[0];
+        assert_eq!(game.name_id, "space-game");
+        assert_eq!(game.keep_installed, None);
+        assert!(game.sessions.is_empty());
+        assert!(game.launch_options.args.is_empty());
+        assert_eq!(game.releases.len(), 2);
+        assert_eq!(game.releases[0].state, ReleaseState::Installed);
+        assert!(!game.releases[0].pinned);
+    }
+
+    #[test]
+    fn migration_to_1_writes_out_defaults() {
+        let mut config = fixture("v0_client_0_1_5.json");
+        add_unversioned_fields(&mut config).unwrap();
+        let game = &config["accounts"][0]["games"][0];
+        assert_eq!(game["sessions"], json!([]));
+        assert_eq!(game["launch_options"]["prefix"], Value::Null);
+        assert_eq!(game["releases"][1]["pinned"], json!(false));
+        assert_eq!(config["max_parallel_downloads"], json!(2));
+    }
+
+    #[test]
+    fn migration_to_2_adds_account_names() {
+        let mut config = fixture("v0_client_0_1_5.json");
+        add_unversioned_fields(&mut config).unwrap();
+        add_account_names(&mut config).unwrap();
+        assert_eq!(config["accounts"][0]["name"], Value::Null);
+        let config = load("v0_client_0_1_5.json");
+        assert_eq!(config.accounts[0].name, None);
+    }
+
+    #[test]
+    fn current_config_is_unchanged() {
+        let config = fixture("v0_client_0_1_5.json");
+        let migrated = migrate(config).unwrap();
+        assert_eq!(migrate(migrated.clone()).unwrap(), migrated);
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let mut config = fixture("v0_client_0_1_5.json");
+        config["schema_version"] = json!(CURRENT_SCHEMA_VERSION + 1);
+        assert!(migrate(config).is_err());
+    }
+
+    #[test]
+    fn rejects_config_without_accounts() {
+        assert!(migrate(json!({"active_account": "", "is_active": false})).is_err());
+    }
+}
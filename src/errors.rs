@@ -1,5 +1,7 @@
 use reqwest::StatusCode;
+use std::fmt::{Display, Formatter};
 use std::io;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub enum LoginError {
@@ -43,6 +45,104 @@ impl From<reqwest::Error> for crate::FetchGamesError {
 
 #[derive(Debug, Clone)]
 pub enum ConfigError {
-    DialogClosed,
     IoError(io::ErrorKind),
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Migration(String),
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(error: serde_json::Error) -> Self {
+        // the position is kept separately, serde_json appends it to the message
+        let message = error.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(i) if error.line() > 0 => message[..i].to_string(),
+            _ => message,
+        };
+        ConfigError::Parse {
+            line: error.line(),
+            column: error.column(),
+            message,
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::IoError(kind) => write!(f, "io error: {}", kind),
+            ConfigError::Parse {
+                line: 0, message, ..
+            } => write!(f, "invalid config: {}", message),
+            ConfigError::Parse {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "invalid config at line {}, column {}: {}",
+                line, column, message
+            ),
+            ConfigError::Migration(message) => write!(f, "failed to migrate config: {}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExtractError {
+    Archive(String),
+    IoError(String),
+    RejectedEntries(Vec<String>),
+    TooLarge { limit: u64 },
+}
+
+impl From<io::Error> for ExtractError {
+    fn from(error: io::Error) -> Self {
+        ExtractError::IoError(error.to_string())
+    }
+}
+
+impl Display for ExtractError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExtractError::Archive(reason) => write!(f, "invalid archive: {}", reason),
+            ExtractError::IoError(reason) => write!(f, "io error: {}", reason),
+            ExtractError::RejectedEntries(entries) => {
+                write!(f, "rejected archive entries: {}", entries.join(", "))
+            }
+            ExtractError::TooLarge { limit } => {
+                write!(f, "archive extracts to more than {} bytes", limit)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LaunchError {
+    MissingExecutable(PathBuf),
+    NotExecutable { path: PathBuf, reason: String },
+    SpawnFailed { path: PathBuf, reason: String },
+    InvalidLaunchPrefix(String),
+}
+
+impl Display for LaunchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LaunchError::MissingExecutable(path) => {
+                write!(f, "found no executable at {}", path.display())
+            }
+            LaunchError::NotExecutable { path, reason } => {
+                write!(f, "{} is not executable: {}", path.display(), reason)
+            }
+            LaunchError::SpawnFailed { path, reason } => {
+                write!(f, "failed to run {}: {}", path.display(), reason)
+            }
+            LaunchError::InvalidLaunchPrefix(reason) => {
+                write!(f, "invalid launch prefix: {}", reason)
+            }
+        }
+    }
 }
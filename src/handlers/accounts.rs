@@ -0,0 +1,374 @@
+use crate::api::can_reach_host;
+use crate::blackboard::Blackboard;
+use crate::client_config::{DropsAccountConfig, SessionToken};
+use crate::handlers::library::LibraryMove;
+use crate::handlers::MessageHandler;
+use crate::messages::Message;
+use crate::Screen;
+use iced::widget::{
+    button, checkbox, column, horizontal_space, row, text, text_input, vertical_space, Column,
+};
+use iced::{Center, Color, Element, Fill, Task};
+use log::{error, info};
+use rfd::FileDialog;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
+enum UrlCheck {
+    Unchanged,
+    Checking,
+    Reachable,
+    Failed(String),
+}
+
+/// Edits of one account, applied to the config on save
+struct AccountEditor {
+    id: Uuid,
+    name: String,
+    url: String,
+    url_check: UrlCheck,
+    games_dir: String,
+    move_games: bool,
+}
+
+impl AccountEditor {
+    fn from_account(account: &DropsAccountConfig) -> Self {
+        AccountEditor {
+            id: account.id,
+            name: account.name.clone().unwrap_or_default(),
+            url: account.url.to_string(),
+            url_check: UrlCheck::Unchanged,
+            games_dir: account.games_dir.to_string(),
+            move_games: true,
+        }
+    }
+
+    fn can_save(&self) -> bool {
+        !self.url.is_empty()
+            && !self.games_dir.is_empty()
+            && matches!(self.url_check, UrlCheck::Unchanged | UrlCheck::Reachable)
+    }
+}
+
+#[derive(Default)]
+pub struct AccountsMessageHandler {
+    editor: Option<AccountEditor>,
+    pending_delete: Option<Uuid>,
+    error: Option<String>,
+}
+
+impl AccountsMessageHandler {
+    pub fn view(&self, blackboard: &Blackboard) -> Element<'_, Message> {
+        let header = row![
+            button(text("back").center()).on_press(Message::GoToInitialScreen),
+            horizontal_space(),
+            text("Accounts").size(32),
+            horizontal_space(),
+        ]
+        .align_y(Center);
+
+        let content = match &self.editor {
+            Some(editor) => self.editor_column(editor, blackboard),
+            None => self.accounts_column(blackboard),
+        };
+
+        column![header, vertical_space().height(20)]
+            .push_maybe(
+                self.error
+                    .as_ref()
+                    .map(|x| text(x.to_string()).color(Color::from_rgb(0.8, 0.4, 0.4))),
+            )
+            .push(content)
+            .padding(10)
+            .spacing(10)
+            .into()
+    }
+
+    fn accounts_column<'a>(&self, blackboard: &Blackboard) -> Column<'a, Message> {
+        let mk_btn = |name, msg| button(text(name).size(12).center()).on_press_maybe(msg);
+        let accounts =
+            blackboard
+                .config
+                .accounts
+                .iter()
+                .fold(column![].spacing(10), |c, account| {
+                    let is_active = account.id == blackboard.config.active_account;
+                    let label = match is_active {
+                        true => format!("{} (active)", account.display_name()),
+                        false => account.display_name(),
+                    };
+                    let actions = match self.pending_delete == Some(account.id) {
+                        true => row![
+                            text("Remove? Installed games stay on disk").size(12),
+                            mk_btn("yes", Some(Message::ConfirmDeleteAccount)),
+                            mk_btn("no", Some(Message::CancelAccountEdit)),
+                        ],
+                        false => row![]
+                            .push_maybe(
+                                is_active
+                                    .then(|| mk_btn("scan library", Some(Message::ScanLibrary))),
+                            )
+                            .push(mk_btn("edit", Some(Message::EditAccount(account.id))))
+                            .push(mk_btn("remove", Some(Message::DeleteAccount(account.id)))),
+                    };
+                    c.push(
+                        row![
+                            column![text(label), text(account.games_dir.to_string()).size(12)],
+                            horizontal_space(),
+                            actions.spacing(10).align_y(Center),
+                        ]
+                        .align_y(Center),
+                    )
+                });
+        column![accounts]
+            .push(vertical_space().height(10))
+            .push(
+                button(text("add account").center()).on_press(Message::GoToScreen(Screen::Wizard)),
+            )
+            .align_x(Center)
+            .width(Fill)
+    }
+
+    fn editor_column<'a>(
+        &self,
+        editor: &'a AccountEditor,
+        blackboard: &Blackboard,
+    ) -> Column<'a, Message> {
+        let mk_input = |label, placeholder, value: &'a str, on_input: fn(String) -> Message| {
+            row![
+                text(label).size(14).width(90),
+                text_input(placeholder, value)
+                    .on_input(on_input)
+                    .size(14)
+                    .width(250),
+            ]
+            .spacing(10)
+            .align_y(Center)
+        };
+        let account = blackboard.config.get_account(editor.id);
+        let url_changed = account.is_some_and(|x| x.url != editor.url);
+        let dir_changed = account.is_some_and(|x| x.games_dir != editor.games_dir);
+        let has_installs = account.is_some_and(|x| !x.installed_games().is_empty());
+
+        let url_status = match &editor.url_check {
+            UrlCheck::Unchanged => text(""),
+            UrlCheck::Checking => text("checking...").size(12),
+            UrlCheck::Reachable => text("ok").size(12).color(Color::from_rgb(0.4, 0.7, 0.4)),
+            UrlCheck::Failed(e) => text(e.to_string())
+                .size(12)
+                .color(Color::from_rgb(0.8, 0.4, 0.4)),
+        };
+        let can_test = url_changed && editor.url_check != UrlCheck::Checking;
+
+        column![
+            mk_input(
+                "name",
+                "user@server",
+                &editor.name,
+                Message::AccountNameChanged
+            ),
+            row![
+                mk_input(
+                    "server",
+                    "drops server url",
+                    &editor.url,
+                    Message::AccountUrlChanged
+                ),
+                button(text("test").size(12).center())
+                    .on_press_maybe(can_test.then_some(Message::TestAccountUrl)),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            url_status,
+            row![
+                text("games dir").size(14).width(90),
+                text(&editor.games_dir).size(14).width(250),
+                button(text("open").size(12).center()).on_press(Message::SelectAccountGamesDir),
+            ]
+            .spacing(10)
+            .align_y(Center),
+        ]
+        // the games of another server are cleared, there is no library to move then
+        .push_maybe((dir_changed && has_installs && !url_changed).then(|| {
+            checkbox("move library", editor.move_games).on_toggle(Message::MoveGamesToggled)
+        }))
+        .push_maybe(url_changed.then(|| {
+            text("Changing the server logs the account out and clears its games").size(12)
+        }))
+        .push(vertical_space().height(10))
+        .push(
+            row![
+                button(text("cancel").center()).on_press(Message::CancelAccountEdit),
+                button(text("save").center())
+                    .on_press_maybe(editor.can_save().then_some(Message::SaveAccount)),
+            ]
+            .spacing(10),
+        )
+        .spacing(10)
+        .align_x(Center)
+        .width(Fill)
+    }
+
+    fn apply_edit(editor: AccountEditor, blackboard: &mut Blackboard) {
+        let name = editor.name.trim().to_string();
+        blackboard
+            .config
+            .update_account_and_save(editor.id, |account| {
+                account.name = (!name.is_empty()).then_some(name);
+                if account.url != editor.url {
+                    // the session and the games belong to the old server
+                    account.url = editor.url;
+                    account.session_token = SessionToken::default();
+                    account.games.clear();
+                }
+                if account.games_dir != editor.games_dir {
+                    account.games_dir = editor.games_dir;
+                    account.refresh_install_states();
+                }
+            });
+        if editor.id == blackboard.config.active_account {
+            blackboard.update_selected_game();
+        }
+    }
+}
+
+impl MessageHandler for AccountsMessageHandler {
+    fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
+        match message {
+            Message::ShowAccounts => {
+                self.editor = None;
+                self.pending_delete = None;
+                self.error = None;
+                blackboard.screen = Screen::Accounts;
+            }
+            Message::EditAccount(id) => {
+                self.error = None;
+                self.pending_delete = None;
+                self.editor = blackboard
+                    .config
+                    .get_account(id)
+                    .map(AccountEditor::from_account);
+            }
+            Message::CancelAccountEdit => {
+                self.editor = None;
+                self.pending_delete = None;
+                self.error = None;
+            }
+            Message::AccountNameChanged(s) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.name = s;
+                }
+            }
+            Message::AccountUrlChanged(s) => {
+                if let Some(editor) = &mut self.editor {
+                    let unchanged = blackboard
+                        .config
+                        .get_account(editor.id)
+                        .is_some_and(|x| x.url == s);
+                    editor.url_check = match unchanged {
+                        true => UrlCheck::Unchanged,
+                        false => UrlCheck::Failed("test the new server first".to_string()),
+                    };
+                    editor.url = s;
+                }
+            }
+            Message::TestAccountUrl => {
+                if let Some(editor) = &mut self.editor {
+                    editor.url_check = UrlCheck::Checking;
+                    return Task::perform(
+                        can_reach_host(editor.url.to_string()),
+                        Message::AccountUrlChecked,
+                    );
+                }
+            }
+            Message::AccountUrlChecked(result) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.url_check = match result {
+                        Ok(()) => UrlCheck::Reachable,
+                        Err(e) => UrlCheck::Failed(e),
+                    };
+                }
+            }
+            Message::SelectAccountGamesDir => {
+                let dir = FileDialog::new().pick_folder();
+                if let (Some(editor), Some(dir)) = (&mut self.editor, dir) {
+                    if let Some(dir) = dir.to_str() {
+                        editor.games_dir = dir.to_string();
+                    }
+                }
+            }
+            Message::MoveGamesToggled(move_games) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.move_games = move_games;
+                }
+            }
+            Message::SaveAccount => {
+                let Some(editor) = self.editor.take() else {
+                    return Task::none();
+                };
+                let Some(account) = blackboard.config.get_account(editor.id) else {
+                    return Task::none();
+                };
+                let url_changed = account.url != editor.url;
+                let dir_changed = account.games_dir != editor.games_dir;
+                // games and downloads of the account write to its games dir and games
+                if (url_changed || dir_changed)
+                    && account.id == blackboard.config.active_account
+                    && blackboard.active_account_busy()
+                {
+                    self.error = Some(
+                        "stop running games and downloads before changing the server or games dir"
+                            .to_string(),
+                    );
+                    self.editor = Some(editor);
+                    return Task::none();
+                }
+                if !dir_changed {
+                    Self::apply_edit(editor, blackboard);
+                    return Task::none();
+                }
+                if !editor.move_games || url_changed || account.installed_games().is_empty() {
+                    Self::apply_edit(editor, blackboard);
+                    return Task::none();
+                }
+                // the games dir changes once the library has moved
+                let library_move = LibraryMove::build(
+                    account,
+                    &editor.games_dir,
+                    blackboard.config.shared_games(account),
+                );
+                Self::apply_edit(
+                    AccountEditor {
+                        games_dir: account.games_dir.to_string(),
+                        ..editor
+                    },
+                    blackboard,
+                );
+                self.error = None;
+                return Task::done(Message::MoveLibrary(library_move));
+            }
+            Message::DeleteAccount(id) => {
+                self.error = None;
+                self.pending_delete = Some(id);
+            }
+            Message::ConfirmDeleteAccount => {
+                let Some(id) = self.pending_delete.take() else {
+                    return Task::none();
+                };
+                if id == blackboard.config.active_account && blackboard.active_account_busy() {
+                    self.error = Some(
+                        "stop running games and downloads before removing the account".to_string(),
+                    );
+                    return Task::none();
+                }
+                info!("removing account {}", id);
+                blackboard.config.remove_account_and_save(id);
+                if !blackboard.have_valid_config() {
+                    blackboard.screen = Screen::Wizard;
+                }
+            }
+            _ => error!("invalid accounts state message: {:?}", message),
+        }
+        Task::none()
+    }
+}
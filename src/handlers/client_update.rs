@@ -62,7 +62,7 @@ pub struct ClientUpdateHandler {
 }
 
 impl ClientUpdateHandler {
-    pub fn view(&self, blackboard: &Blackboard) -> Element<Message> {
+    pub fn view(&self, blackboard: &Blackboard) -> Element<'_, Message> {
         match &blackboard.screen {
             Screen::ClientUpdateAvailable(new_release) => match &self.state {
                 ClientUpdateState::IsUpdating => {
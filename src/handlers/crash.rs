@@ -0,0 +1,272 @@
+use crate::api;
+use crate::blackboard::Blackboard;
+use crate::client_config::get_config_dir;
+use crate::handlers::logs;
+use crate::handlers::playing::GameExit;
+use crate::handlers::MessageHandler;
+use crate::messages::Message;
+use crate::{view_utils, Screen};
+use chrono::{DateTime, Utc};
+use iced::widget::{button, column, row, text, vertical_space};
+use iced::{Center, Element, Subscription, Task};
+use log::{error, info};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use sysinfo::System;
+use uuid::Uuid;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+pub fn get_crash_reports_dir() -> PathBuf {
+    get_config_dir().join("crash_reports")
+}
+
+/// An abnormal exit of a game, waiting for the user to decide whether to report it
+#[derive(Debug, Clone)]
+pub struct Crash {
+    pub game_name: String,
+    pub game_name_id: String,
+    pub version: String,
+    pub channel_name: String,
+    pub exit: GameExit,
+    pub crashed_at: DateTime<Utc>,
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct SystemReport {
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub kernel_version: Option<String>,
+    pub cpu_arch: Option<String>,
+    pub cpu: Option<String>,
+    pub cpu_cores: Option<usize>,
+    pub total_memory_bytes: u64,
+}
+
+impl SystemReport {
+    fn collect() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu_all();
+        system.refresh_memory();
+        SystemReport {
+            os: System::name(),
+            os_version: System::long_os_version(),
+            kernel_version: System::kernel_version(),
+            cpu_arch: System::cpu_arch(),
+            cpu: system.cpus().first().map(|x| x.brand().to_string()),
+            cpu_cores: system.physical_core_count(),
+            total_memory_bytes: system.total_memory(),
+        }
+    }
+}
+
+/// Body of the crash report sent to the drops server
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct CrashReport {
+    pub id: Uuid,
+    pub game_name_id: String,
+    pub version: String,
+    pub channel_name: String,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub crashed_at: DateTime<Utc>,
+    pub system: SystemReport,
+    pub log: String,
+}
+
+/// A report waiting to be sent. Reports are kept on disk until the server they belong
+/// to has accepted them, so they survive restarts of the client.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct QueuedCrashReport {
+    drops_url: String,
+    report: CrashReport,
+}
+
+impl QueuedCrashReport {
+    fn path(&self) -> PathBuf {
+        get_crash_reports_dir().join(format!("{}.json", self.report.id))
+    }
+}
+
+async fn queue_crash_report(crash: Crash, drops_url: String) -> Result<QueuedCrashReport, String> {
+    let log = match &crash.log_file {
+        None => String::new(),
+        Some(path) => logs::read_log_tail(path.clone()).await.unwrap_or_else(|e| {
+            error!("failed to read log for crash report: {}", e);
+            String::new()
+        }),
+    };
+    let queued = QueuedCrashReport {
+        drops_url,
+        report: CrashReport {
+            id: Uuid::new_v4(),
+            game_name_id: crash.game_name_id,
+            version: crash.version,
+            channel_name: crash.channel_name,
+            exit_code: crash.exit.code,
+            signal: crash.exit.signal,
+            crashed_at: crash.crashed_at,
+            system: SystemReport::collect(),
+            log,
+        },
+    };
+    let json = serde_json::to_string(&queued).map_err(|e| e.to_string())?;
+    tokio::fs::create_dir_all(get_crash_reports_dir())
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::fs::write(queued.path(), json)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(queued)
+}
+
+fn load_queued_reports() -> Vec<QueuedCrashReport> {
+    let Ok(entries) = fs::read_dir(get_crash_reports_dir()) else {
+        return vec![];
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|x| x.path().extension().is_some_and(|x| x == "json"))
+        .filter_map(|x| {
+            let queued = fs::read_to_string(x.path())
+                .map_err(|e| e.to_string())
+                .and_then(|x| serde_json::from_str(&x).map_err(|e| e.to_string()));
+            if let Err(e) = &queued {
+                error!("ignoring crash report {}: {}", x.path().display(), e);
+            }
+            queued.ok()
+        })
+        .collect()
+}
+
+/// Sends the reports in order and stops at the first failure, the server is most likely
+/// unreachable then. Returns the ids of the reports that were accepted.
+async fn send_crash_reports(reports: Vec<QueuedCrashReport>, session_token: String) -> Vec<Uuid> {
+    let mut sent = vec![];
+    for queued in reports {
+        if let Err(e) =
+            api::send_crash_report(&queued.drops_url, &session_token, &queued.report).await
+        {
+            info!("failed to send crash report {}: {}", queued.report.id, e);
+            break;
+        }
+        if let Err(e) = tokio::fs::remove_file(queued.path()).await {
+            error!("failed to remove sent crash report: {}", e);
+        }
+        sent.push(queued.report.id);
+    }
+    sent
+}
+
+#[derive(Default)]
+pub struct CrashMessageHandler {
+    crash: Option<Crash>,
+    queued: Vec<QueuedCrashReport>,
+    loaded_queue: bool,
+    sending: bool,
+}
+
+impl CrashMessageHandler {
+    pub fn view(&self, _: &Blackboard) -> Element<'_, Message> {
+        let Some(crash) = &self.crash else {
+            return column![].into();
+        };
+        view_utils::container_with_title(
+            format!("{} crashed", crash.game_name),
+            column![
+                text(format!(
+                    "{} {} {}",
+                    crash.channel_name, crash.version, crash.exit
+                )),
+                text("The report includes the session log, the release and basic system info")
+                    .size(12),
+                vertical_space().height(20),
+                row![
+                    button(text("send report").center()).on_press(Message::SubmitCrashReport),
+                    button(text("view log").center())
+                        .on_press_maybe(crash.log_file.clone().map(|x| Message::ShowLogs(Some(x)))),
+                    button(text("dismiss").center()).on_press(Message::DismissCrash),
+                ]
+                .spacing(10),
+            ]
+            .align_x(Center),
+        )
+    }
+
+    pub(crate) fn subscription(&self) -> Subscription<Message> {
+        match self.queued.is_empty() {
+            true => Subscription::none(),
+            false => iced::time::every(RETRY_INTERVAL).map(|_| Message::SendCrashReports),
+        }
+    }
+}
+
+impl MessageHandler for CrashMessageHandler {
+    fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
+        match message {
+            Message::GameCrashed(crash) => {
+                info!("{} crashed: {}", crash.game_name_id, crash.exit);
+                self.crash = Some(crash);
+                blackboard.screen = Screen::CrashReport;
+            }
+            Message::DismissCrash => {
+                self.crash = None;
+                blackboard.screen = Screen::Main;
+            }
+            Message::SubmitCrashReport => {
+                blackboard.screen = Screen::Main;
+                if let Some(crash) = self.crash.take() {
+                    return Task::perform(
+                        queue_crash_report(crash, blackboard.config.get_drops_url()),
+                        Message::CrashReportQueued,
+                    );
+                }
+            }
+            Message::CrashReportQueued(Ok(queued)) => {
+                self.queued.push(queued);
+                return Task::done(Message::SendCrashReports);
+            }
+            Message::CrashReportQueued(Err(e)) => {
+                error!("failed to queue crash report: {}", e);
+                blackboard.status_message = Some(format!("failed to save crash report: {}", e));
+            }
+            Message::SendCrashReports => {
+                if !self.loaded_queue {
+                    self.loaded_queue = true;
+                    self.queued.extend(load_queued_reports());
+                }
+                if self.sending || !blackboard.config.has_session_token() {
+                    return Task::none();
+                }
+                // reports are sent with the session of the server they were made for
+                let drops_url = blackboard.config.get_drops_url();
+                let reports: Vec<QueuedCrashReport> = self
+                    .queued
+                    .iter()
+                    .filter(|x| x.drops_url == drops_url)
+                    .cloned()
+                    .collect();
+                if reports.is_empty() {
+                    return Task::none();
+                }
+                self.sending = true;
+                return Task::perform(
+                    send_crash_reports(reports, blackboard.config.get_session_token().to_string()),
+                    Message::CrashReportsSent,
+                );
+            }
+            Message::CrashReportsSent(sent) => {
+                self.sending = false;
+                self.queued.retain(|x| !sent.contains(&x.report.id));
+                blackboard.status_message = Some(match self.queued.len() {
+                    0 => "crash report sent, thanks!".to_string(),
+                    queued => format!("{} crash report(s) waiting for the server", queued),
+                });
+            }
+            _ => error!("invalid crash state message: {:?}", message),
+        }
+        Task::none()
+    }
+}
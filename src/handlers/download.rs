@@ -1,20 +1,32 @@
-use crate::api::{unzip_file, InstalledRelease};
+use crate::api::{
+    content_range_start, content_range_total, fetch_release, fetch_release_checksum, unzip_file,
+    InstalledRelease, MAX_EXTRACT_RATIO,
+};
 use crate::blackboard::Blackboard;
 use crate::client_config::ReleaseState::Installed;
 use crate::client_config::{ClientConfig, Game, Release, SessionToken};
+use crate::errors::ExtractError;
+use crate::handlers::games::remove_release_dirs;
 use crate::handlers::MessageHandler;
 use crate::messages::Message;
-use crate::{utils, view_utils, Screen};
+use crate::{utils, Screen};
 use futures_util::{SinkExt, Stream, StreamExt};
-use iced::widget::{button, column, progress_bar, text, vertical_space};
-use iced::{Center, Element, Fill, Task};
+use iced::widget::{
+    button, column, horizontal_space, pick_list, progress_bar, row, scrollable, text,
+    vertical_space,
+};
+use iced::{Center, Color, Element, Fill, Task};
+use iced_futures::futures::channel::mpsc::Sender;
 use iced_futures::stream::try_channel;
 use iced_futures::Subscription;
-use log::error;
+use log::{error, info};
+use reqwest::StatusCode;
+use std::collections::HashSet;
 use std::fs;
-use std::io::Cursor;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufWriter};
 use zip::ZipArchive;
 
 #[cfg(windows)]
@@ -26,6 +38,7 @@ use std::io::Write;
 #[derive(Debug, Clone)]
 pub struct DownloadRequest {
     name_id: String,
+    name: String,
     game_dir: String,
     drops_url: String,
     session_token: SessionToken,
@@ -38,6 +51,7 @@ impl DownloadRequest {
     pub fn build(release: &Release, game: &Game, config: &ClientConfig) -> DownloadRequest {
         DownloadRequest {
             name_id: game.name_id.to_string(),
+            name: game.name.to_string(),
             game_dir: config.get_games_dir(),
             drops_url: config.get_drops_url(),
             session_token: config.get_session_token(),
@@ -53,22 +67,38 @@ pub enum DownloadError {
     RequestFailed(Arc<reqwest::Error>),
     EmptyResponse,
     IoError(String),
+    ChecksumMismatch { expected: String, actual: String },
+    ChecksumUnavailable,
+    ExtractFailed(ExtractError),
 }
 impl From<reqwest::Error> for DownloadError {
     fn from(error: reqwest::Error) -> Self {
         DownloadError::RequestFailed(Arc::new(error))
     }
 }
+impl From<std::io::Error> for DownloadError {
+    fn from(error: std::io::Error) -> Self {
+        DownloadError::IoError(error.to_string())
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum DownloadProgress {
-    Downloading { percent: f32 },
-    Finished { release: InstalledRelease },
+    Downloading {
+        percent: f32,
+    },
+    /// All bytes are transferred, the archive is verified and extracted
+    Extracting,
+    Finished {
+        release: InstalledRelease,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub struct Download {
+    pub(crate) id: String,
     pub(crate) game_name_id: String,
+    game_name: String,
     game_dir: String,
     url: String,
     session_token: SessionToken,
@@ -81,19 +111,32 @@ pub struct Download {
 impl Download {
     pub fn new(request: &DownloadRequest) -> Self {
         Self {
+            id: Self::build_id(&request.name_id, &request.channel_name, &request.version),
             game_name_id: request.name_id.to_string(),
+            game_name: request.name.to_string(),
             game_dir: request.game_dir.to_string(),
             url: request.drops_url.to_string(),
             session_token: request.session_token.clone(),
             version: request.version.to_string(),
             channel_name: request.channel_name.to_string(),
-            state: DownloadState::Downloading {
-                progress_percentage: 0.0,
-            },
+            state: DownloadState::Queued,
             size_bytes: request.size_bytes,
         }
     }
 
+    pub(crate) fn build_id(game_name_id: &str, channel_name: &str, version: &str) -> String {
+        utils::release_id(game_name_id, channel_name, version)
+    }
+
+    fn partial_path(&self) -> PathBuf {
+        utils::get_partial_download_path(
+            &self.game_dir,
+            &self.game_name_id,
+            &self.channel_name,
+            &self.version,
+        )
+    }
+
     pub fn download(&self) -> impl Stream<Item = Result<DownloadProgress, DownloadError>> {
         let url = format!(
             "{}/releases/{}/{}/{}/{}",
@@ -115,36 +158,68 @@ impl Download {
             version: self.version.to_string(),
             channel_name: self.channel_name.to_string(),
         };
+        let partial_path = self.partial_path();
+        let extract_dir = utils::get_extract_path(
+            &self.game_dir,
+            &self.game_name_id,
+            &self.channel_name,
+            &self.version,
+        );
         let content_length = self.size_bytes;
         try_channel(1, move |mut output| async move {
+            // Bytes from an earlier attempt are kept next to the install target, so a
+            // dropped connection or a restart of the client can continue from there.
+            let mut resume_from = tokio::fs::metadata(&partial_path)
+                .await
+                .map(|x| x.len())
+                .unwrap_or(0);
+            let total = content_length;
+            let percent = |downloaded: u64| 100.0 * (downloaded as f32 / total as f32);
             let _ = output
-                .send(DownloadProgress::Downloading { percent: 0.0 })
+                .send(DownloadProgress::Downloading {
+                    percent: percent(resume_from),
+                })
                 .await;
-            let client = crate::api::build_client();
-            let response = client.get(&url).header("cookie", token).send().await?;
 
-            let stream = response.bytes_stream();
-            tokio::pin!(stream); // Pin the stream for iteration
-            let mut downloaded = 0;
-            let total = content_length;
-            let mut zip_data = Vec::new();
-            while let Some(Ok(chunk)) = stream.next().await {
-                downloaded += chunk.len();
-                zip_data.extend_from_slice(&chunk);
-                let percent = 100.0 * (downloaded as f32 / total as f32);
-                let _ = output.send(DownloadProgress::Downloading { percent }).await;
+            // Without a digest the archive can't be trusted, fail before transferring it
+            let Some(expected) = fetch_release_checksum(&url, &token).await? else {
+                return Err(DownloadError::ChecksumUnavailable);
+            };
+
+            let mut response = fetch_release(&url, &token, resume_from).await?;
+            // The partial file may already hold the whole release, e.g. when the client
+            // was closed while verifying it. It only needs to be verified then.
+            let complete = response.status() == StatusCode::RANGE_NOT_SATISFIABLE
+                && content_range_total(&response).unwrap_or(content_length) == resume_from;
+            if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && !complete {
+                info!("partial download of {} is not usable, restarting", &url);
+                resume_from = 0;
+                response = fetch_release(&url, &token, resume_from).await?;
             }
-            if downloaded == 0 {
-                return Err(DownloadError::EmptyResponse);
+            if !complete {
+                Self::transfer(response, &partial_path, resume_from, &mut output, percent).await?;
             }
+            let _ = output.send(DownloadProgress::Extracting).await;
 
-            let reader = Cursor::new(zip_data);
-            let mut zip =
-                ZipArchive::new(reader).map_err(|e| DownloadError::IoError(e.to_string()))?;
+            let actual = {
+                let partial_path = partial_path.clone();
+                tokio::task::spawn_blocking(move || utils::sha256_file(&partial_path))
+                    .await
+                    .map_err(|e| DownloadError::IoError(e.to_string()))??
+            };
+            if actual != expected {
+                // The data can not be trusted, so don't resume from it next time
+                fs::remove_file(&partial_path)?;
+                return Err(DownloadError::ChecksumMismatch { expected, actual });
+            }
 
-            fs::create_dir_all(&output_dir).expect("failed creating unzip folder");
-            let output_dir = output_dir.as_path().to_str().unwrap();
-            unzip_file(&mut zip, output_dir).map_err(|e| DownloadError::IoError(e.to_string()))?;
+            // the size the server declares for the release, not whatever arrived
+            let max_size = content_length * MAX_EXTRACT_RATIO;
+            tokio::task::spawn_blocking(move || {
+                Self::install_archive(&partial_path, &extract_dir, &output_dir, max_size)
+            })
+            .await
+            .map_err(|e| DownloadError::IoError(e.to_string()))??;
 
             output
                 .send(DownloadProgress::Finished { release })
@@ -155,10 +230,85 @@ impl Download {
         })
     }
 
+    /// Extracts a verified archive next to the install dir and swaps it into place, so a
+    /// failure leaves an existing install of the version untouched and no half extracted
+    /// release behind
+    fn install_archive(
+        partial_path: &Path,
+        extract_dir: &Path,
+        output_dir: &Path,
+        max_size: u64,
+    ) -> Result<(), DownloadError> {
+        let mut zip = ZipArchive::new(fs::File::open(partial_path)?)
+            .map_err(|e| DownloadError::IoError(e.to_string()))?;
+        let _ = fs::remove_dir_all(extract_dir);
+        fs::create_dir_all(extract_dir)?;
+        if let Err(e) = unzip_file(&mut zip, extract_dir, max_size) {
+            let _ = fs::remove_dir_all(extract_dir);
+            return Err(DownloadError::ExtractFailed(e));
+        }
+        if output_dir.exists() {
+            fs::remove_dir_all(output_dir)?;
+        }
+        fs::rename(extract_dir, output_dir)?;
+        fs::remove_file(partial_path)?;
+        Ok(())
+    }
+
+    /// Writes the release from `response` to the partial file, appending to it if the
+    /// server continued at `resume_from`.
+    async fn transfer(
+        response: reqwest::Response,
+        partial_path: &Path,
+        resume_from: u64,
+        output: &mut Sender<DownloadProgress>,
+        percent: impl Fn(u64) -> f32,
+    ) -> Result<(), DownloadError> {
+        let response = response.error_for_status()?;
+        let resumed = resume_from > 0 && content_range_start(&response) == Some(resume_from);
+        if resume_from > 0 && !resumed {
+            info!(
+                "server ignored range request for {}, downloading all",
+                response.url()
+            );
+        }
+
+        if let Some(parent) = partial_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let archive_file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(partial_path)
+            .await?;
+        let mut writer = BufWriter::new(archive_file);
+
+        let stream = response.bytes_stream();
+        tokio::pin!(stream); // Pin the stream for iteration
+        let mut downloaded = if resumed { resume_from } else { 0 };
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            downloaded += chunk.len() as u64;
+            writer.write_all(&chunk).await?;
+            let _ = output
+                .send(DownloadProgress::Downloading {
+                    percent: percent(downloaded),
+                })
+                .await;
+        }
+        writer.flush().await?;
+        if downloaded == 0 {
+            return Err(DownloadError::EmptyResponse);
+        }
+        Ok(())
+    }
+
     pub fn subscription(&self) -> Subscription<Message> {
         match self.state {
-            DownloadState::Downloading { .. } => {
-                let id = self.game_name_id.to_string();
+            DownloadState::Downloading { .. } | DownloadState::Extracting => {
+                let id = self.id.to_string();
                 Subscription::run_with_id(
                     id.to_string(),
                     self.download()
@@ -173,13 +323,112 @@ impl Download {
 
 #[derive(Debug, Clone)]
 pub enum DownloadState {
-    Downloading { progress_percentage: f32 },
+    Queued,
+    Downloading {
+        progress_percentage: f32,
+    },
+    Paused {
+        progress_percentage: f32,
+    },
+    /// Downloaded, being verified and extracted. It can't be paused or cancelled anymore.
+    Extracting,
     Errored(DownloadError),
+    /// Downloaded, files of the previous version are being carried over
+    CarryingOver,
+    Finished,
 }
 
+/// Removing the partial file is retried for a while, as the download being cancelled
+/// might still have it open for a moment.
+async fn remove_partial_download(path: PathBuf) {
+    for _ in 0..10 {
+        match tokio::fs::remove_file(&path).await {
+            Ok(_) => return,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(_) => tokio::time::sleep(Duration::from_millis(200)).await,
+        }
+    }
+    error!("failed to remove partial download {}", path.display());
+}
+
+/// Ids of the releases of the given games that have a partial download in the games dir
+fn find_partial_downloads(games_dir: &str, game_name_ids: &[String]) -> HashSet<String> {
+    let mut ids = HashSet::new();
+    for game_name_id in game_name_ids {
+        let Ok(channels) = fs::read_dir(utils::get_download_dir(games_dir, game_name_id)) else {
+            continue;
+        };
+        for channel in channels.filter_map(Result::ok) {
+            let Ok(entries) = fs::read_dir(channel.path()) else {
+                continue;
+            };
+            let channel_name = channel.file_name().to_string_lossy().to_string();
+            for entry in entries.filter_map(Result::ok) {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some(version) = name.strip_suffix(utils::PARTIAL_DOWNLOAD_SUFFIX) {
+                    ids.insert(Download::build_id(game_name_id, &channel_name, version));
+                }
+            }
+        }
+    }
+    ids
+}
+
+/// Copies the files matching the carry-over patterns of a game from the previous version
+/// into a new one. Directories that match are copied as a whole.
+fn carry_over_files(from: &Path, to: &Path, patterns: &[String]) -> std::io::Result<usize> {
+    // copying a file onto itself truncates it
+    if from == to {
+        return Ok(0);
+    }
+    fn visit(dir: &Path, relative: &str, files: &mut Vec<String>) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative = match relative.is_empty() {
+                true => name,
+                false => format!("{}/{}", relative, name),
+            };
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                visit(&entry.path(), &relative, files)?;
+            } else if file_type.is_file() {
+                files.push(relative);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    visit(from, "", &mut files)?;
+    let mut copied = 0;
+    for file in files {
+        let components: Vec<&str> = file.split('/').collect();
+        let matches = (1..=components.len()).any(|i| {
+            let path = components[..i].join("/");
+            patterns.iter().any(|x| utils::glob_match(x, &path))
+        });
+        if !matches {
+            continue;
+        }
+        let target = to.join(&file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(from.join(&file), &target)?;
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+const MAX_PARALLEL_DOWNLOADS_OPTION: usize = 5;
+
 #[derive(Default)]
 pub struct DownloadMessageHandler {
     pub(crate) downloads: Vec<Download>,
+    /// Releases with a partial download to resume from, kept up to date by the download
+    /// events so views don't have to look at the disk
+    resumable: HashSet<String>,
 }
 
 impl DownloadMessageHandler {
@@ -244,145 +493,537 @@ Categories=Game;"#,
         Ok(file_path)
     }
 
-    pub fn view(&self, blackboard: &Blackboard) -> Element<Message> {
-        let displayed_download = match &blackboard.selected_game {
-            None => None,
-            Some(game) => {
-                let state = self
-                    .downloads
-                    .iter()
-                    .find(|x| x.game_name_id == game.name_id);
-                match state {
-                    None => None,
-                    Some(download) => Some(download),
-                }
+    pub(crate) fn error_reason(error: &DownloadError) -> String {
+        match error {
+            DownloadError::EmptyResponse => "received empty response from server".to_string(),
+            DownloadError::RequestFailed(e) => format!("request error:  {}", e),
+            DownloadError::IoError(reason) => format!("IO error:  {}", reason),
+            DownloadError::ChecksumMismatch { expected, actual } => format!(
+                "checksum mismatch, expected {} but got {}",
+                expected, actual
+            ),
+            DownloadError::ChecksumUnavailable => {
+                "server published no checksum for the release".to_string()
             }
-        };
-        if displayed_download.is_none() {
-            return column![].into();
+            DownloadError::ExtractFailed(e) => format!("extraction failed: {}", e),
         }
+    }
+
+    pub(crate) fn get_download(
+        &self,
+        game_name_id: &str,
+        channel_name: &str,
+        version: &str,
+    ) -> Option<&Download> {
+        let id = Download::build_id(game_name_id, channel_name, version);
+        self.downloads.iter().find(|x| x.id == id)
+    }
+
+    pub(crate) fn has_partial_download(
+        &self,
+        game_name_id: &str,
+        channel_name: &str,
+        version: &str,
+    ) -> bool {
+        let id = Download::build_id(game_name_id, channel_name, version);
+        self.resumable.contains(&id)
+    }
+
+    /// Looks for partial downloads left by earlier runs of the client
+    pub(crate) fn scan_partial_downloads(config: &ClientConfig) -> Task<Message> {
+        let games_dir = config.get_games_dir();
+        let game_name_ids: Vec<String> = config
+            .get_account_games()
+            .into_iter()
+            .map(|x| x.name_id.to_string())
+            .collect();
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    find_partial_downloads(&games_dir, &game_name_ids)
+                })
+                .await
+                .unwrap_or_default()
+            },
+            Message::PartialDownloadsFound,
+        )
+    }
 
-        let displayed_download = displayed_download.unwrap();
-        match &displayed_download.state {
+    /// Downloads that are running or waiting for a free slot
+    fn pending(&self) -> impl Iterator<Item = &Download> {
+        self.downloads.iter().filter(|x| {
+            matches!(
+                x.state,
+                DownloadState::Queued
+                    | DownloadState::Downloading { .. }
+                    | DownloadState::Extracting
+                    | DownloadState::CarryingOver
+            )
+        })
+    }
+
+    pub(crate) fn pending_count(&self) -> usize {
+        self.pending().count()
+    }
+
+    pub(crate) fn pending_ids(&self) -> Vec<String> {
+        self.pending().map(|x| x.id.to_string()).collect()
+    }
+
+    /// Points the paused and failed downloads of a moved library at the new games dir,
+    /// their partial files were moved along
+    pub(crate) fn move_library(&mut self, from: &str, to: &str) {
+        let ids = self.pending_ids();
+        for download in self.downloads.iter_mut() {
+            if download.game_dir == from && !ids.contains(&download.id) {
+                download.game_dir = to.to_string();
+            }
+        }
+    }
+
+    /// 1-based position of a queued download, counted in the order they were requested
+    pub(crate) fn queue_position(&self, id: &str) -> Option<usize> {
+        self.downloads
+            .iter()
+            .filter(|x| matches!(x.state, DownloadState::Queued))
+            .position(|x| x.id == id)
+            .map(|x| x + 1)
+    }
+
+    fn start_queued_downloads(&mut self, max_parallel_downloads: usize) {
+        let running = self
+            .downloads
+            .iter()
+            .filter(|x| {
+                matches!(
+                    x.state,
+                    DownloadState::Downloading { .. } | DownloadState::Extracting
+                )
+            })
+            .count();
+        self.downloads
+            .iter_mut()
+            .filter(|x| matches!(x.state, DownloadState::Queued))
+            .take(max_parallel_downloads.saturating_sub(running))
+            .for_each(|x| {
+                x.state = DownloadState::Downloading {
+                    progress_percentage: 0.0,
+                }
+            });
+    }
+
+    fn download_row(&self, download: &Download) -> Element<'_, Message> {
+        let title = column![
+            text(download.game_name.to_string()).size(16),
+            text(format!("{} {}", download.channel_name, download.version)).size(12),
+        ]
+        .width(170);
+
+        let id = download.id.to_string();
+        let mk_btn = |name, msg| button(text(name).size(12).center()).on_press(msg);
+        let status: Element<Message> = match &download.state {
+            DownloadState::Queued => row![
+                text(format!(
+                    "Queued #{}",
+                    self.queue_position(&download.id).unwrap_or_default()
+                ))
+                .size(14)
+                .width(150),
+                mk_btn("Cancel", Message::CancelDownload(id)),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
             DownloadState::Downloading {
-                progress_percentage: progress,
-            } => iced::widget::column![
-                vertical_space().height(150),
-                text("Downloading Release").size(24),
-                vertical_space().height(50),
-                text(format!("{:.1}%", progress)).size(14).align_x(Center),
-                progress_bar(0.0..=100.0, progress.clone()).width(200)
+                progress_percentage,
+            } => row![
+                progress_bar(0.0..=100.0, *progress_percentage)
+                    .width(150)
+                    .height(10),
+                text(format!("{:.1}%", progress_percentage)).size(12),
+                mk_btn("Pause", Message::PauseDownload(id.to_string())),
+                mk_btn("Cancel", Message::CancelDownload(id)),
             ]
-            .align_x(Center)
-            .width(Fill)
+            .spacing(10)
+            .align_y(Center)
             .into(),
-            DownloadState::Errored(reason) => {
-                let reason_str = match reason {
-                    DownloadError::EmptyResponse => {
-                        "received empty response from server".to_string()
-                    }
-                    DownloadError::RequestFailed(e) => format!("request error:  {}", e),
-                    DownloadError::IoError(reason) => format!("IO error:  {}", reason),
-                };
-                let game_name_id = displayed_download.game_name_id.to_string();
-                view_utils::centered_container(
-                    column![
-                        text(format!(
-                            "Failed to download release with error: {}",
-                            reason_str
-                        )),
-                        button(text("Ok").center())
-                            .on_press(Message::CloseDownloadError(game_name_id))
-                    ]
-                    .align_x(Center)
-                    .width(300)
-                    .into(),
-                )
-            }
-        }
+            DownloadState::Paused {
+                progress_percentage,
+            } => row![
+                text(format!("Paused at {:.1}%", progress_percentage))
+                    .size(14)
+                    .width(150),
+                mk_btn("Resume", Message::ResumeDownload(id.to_string())),
+                mk_btn("Cancel", Message::CancelDownload(id)),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+            DownloadState::Errored(error) => row![
+                text(Self::error_reason(error))
+                    .size(12)
+                    .width(150)
+                    .color(Color::from_rgb(0.8, 0.4, 0.4)),
+                mk_btn("Retry", Message::RetryDownload(id.to_string())),
+                mk_btn("Remove", Message::RemoveDownload(id)),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+            DownloadState::Extracting => row![
+                text("Installing").size(14).width(150),
+                button(text("Pause").size(12).center()),
+                button(text("Cancel").size(12).center()),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+            DownloadState::CarryingOver => row![text("Copying files").size(14).width(150)]
+                .align_y(Center)
+                .into(),
+            DownloadState::Finished => row![
+                text("Installed").size(14).width(150),
+                mk_btn("Remove", Message::RemoveDownload(id)),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+        };
+
+        row![title, status].spacing(10).align_y(Center).into()
+    }
+
+    pub fn view(&self, blackboard: &Blackboard) -> Element<'_, Message> {
+        let max_parallel_downloads = pick_list(
+            (1..=MAX_PARALLEL_DOWNLOADS_OPTION).collect::<Vec<usize>>(),
+            Some(blackboard.config.max_parallel_downloads),
+            Message::MaxParallelDownloadsChanged,
+        )
+        .width(70);
+
+        let header = row![
+            button(text("back").center()).on_press(Message::GoToScreen(Screen::Main)),
+            horizontal_space(),
+            text("Downloads").size(32),
+            horizontal_space(),
+            text("parallel").size(14),
+            max_parallel_downloads,
+        ]
+        .spacing(10)
+        .align_y(Center);
+
+        let downloads: Element<Message> = match self.downloads.is_empty() {
+            true => text("No downloads").into(),
+            false => scrollable(
+                column(self.downloads.iter().map(|x| self.download_row(x)))
+                    .spacing(15)
+                    .padding(10),
+            )
+            .height(Fill)
+            .into(),
+        };
+
+        let has_finished = self
+            .downloads
+            .iter()
+            .any(|x| matches!(x.state, DownloadState::Finished));
+        let clear_button = button(text("clear finished").center())
+            .on_press_maybe(has_finished.then_some(Message::ClearFinishedDownloads));
+
+        column![
+            header,
+            vertical_space().height(20),
+            downloads,
+            row![horizontal_space(), clear_button]
+        ]
+        .padding(20)
+        .width(Fill)
+        .height(Fill)
+        .into()
     }
     pub(crate) fn subscription(&self) -> Subscription<Message> {
         Subscription::batch(self.downloads.iter().map(Download::subscription))
     }
+
+    /// Carries files over from the newest installed version of the same channel, the new
+    /// release is only marked as installed afterwards.
+    fn carry_over(release: InstalledRelease, blackboard: &Blackboard) -> Task<Message> {
+        let games = blackboard.config.get_account_games();
+        let previous = games
+            .iter()
+            .find(|x| x.name_id == release.game_name_id)
+            .filter(|x| !x.carry_over.is_empty())
+            .and_then(|game| {
+                let installing = game.releases.iter().find(|x| {
+                    x.channel_name == release.channel_name && x.version == release.version
+                })?;
+                // the newest install that is older than this one, a downgrade must not
+                // carry files back from a newer build
+                let older: Vec<Release> = game
+                    .releases
+                    .iter()
+                    .filter(|x| x.version != release.version)
+                    .filter(|x| x.release_date < installing.release_date)
+                    .cloned()
+                    .collect();
+                let previous = utils::newest_release_by_state(
+                    &older,
+                    Some(&release.channel_name),
+                    Some(Installed),
+                )?;
+                Some((game.carry_over.clone(), previous))
+            });
+        let Some((patterns, previous)) = previous else {
+            return Task::done(Message::CarriedOver((release, Ok(0))));
+        };
+        let games_dir = blackboard.config.get_games_dir();
+        let from = utils::get_exe_path(
+            &games_dir,
+            &release.game_name_id,
+            &previous.channel_name,
+            &previous.version,
+        );
+        let to = utils::get_exe_path(
+            &games_dir,
+            &release.game_name_id,
+            &release.channel_name,
+            &release.version,
+        );
+        info!(
+            "carrying over files from {} to {}",
+            from.display(),
+            to.display()
+        );
+        Task::perform(
+            async move {
+                let result =
+                    tokio::task::spawn_blocking(move || carry_over_files(&from, &to, &patterns))
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|x| x.map_err(|e| e.to_string()));
+                (release, result)
+            },
+            Message::CarriedOver,
+        )
+    }
+
+    fn handle_finished(
+        &mut self,
+        release: InstalledRelease,
+        blackboard: &mut Blackboard,
+    ) -> Task<Message> {
+        if blackboard
+            .config
+            .update_install_state(
+                &release.game_name_id,
+                &release.version,
+                &release.channel_name,
+                Installed,
+            )
+            .is_err()
+        {
+            blackboard.screen = Screen::Error("failed to update config install state".to_string());
+            return Task::none();
+        }
+        blackboard.update_selected_game();
+        let is_selected_game = blackboard
+            .selected_game
+            .as_ref()
+            .is_some_and(|x| x.name_id == release.game_name_id);
+        if is_selected_game && blackboard.selected_version.is_none() {
+            blackboard.selected_version = Some(release.version.to_string());
+        }
+        blackboard.config.save().expect("failed to save config!");
+
+        let games = blackboard.config.get_account_games();
+        if let Some(game) = games.iter().find(|x| x.name_id == release.game_name_id) {
+            if game.app_link.is_none() {
+                Self::create_app_link(game, blackboard);
+            }
+        }
+
+        let to_prune = blackboard.releases_to_prune(
+            &release.game_name_id,
+            &release.channel_name,
+            &release.version,
+        );
+        if to_prune.is_empty() {
+            return Task::none();
+        }
+        info!(
+            "removing {} old releases of {}",
+            to_prune.len(),
+            &release.game_name_id
+        );
+        Task::perform(
+            remove_release_dirs(
+                blackboard.config.get_games_dir(),
+                release.game_name_id,
+                to_prune,
+            ),
+            Message::ReleasesUninstalled,
+        )
+    }
+
+    fn create_app_link(game: &Game, blackboard: &mut Blackboard) {
+        #[cfg(windows)]
+        match Self::create_windows_start_menu_entry(&game.name_id, &game.name) {
+            Ok(path) => blackboard.config.set_app_link(&game.name_id, Some(path)),
+            Err(e) => {
+                blackboard.screen =
+                    Screen::Error(format!("failed to create windows start menu entry: {}", e));
+            }
+        }
+
+        #[cfg(unix)]
+        match Self::create_linux_desktop_entry(&game.name_id, &game.name) {
+            Ok(path) => blackboard.config.set_app_link(&game.name_id, Some(path)),
+            Err(e) => error!("failed to create linux desktop entry: {}", e),
+        }
+    }
 }
 
 impl MessageHandler for DownloadMessageHandler {
     fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
         match message {
             Message::Download(request) => {
-                self.downloads.push(Download::new(&request));
-                blackboard.screen = Screen::Downloading;
-            }
-            Message::DownloadProgressing((id, Ok(progress))) => match progress {
-                DownloadProgress::Downloading { percent } => {
-                    self.downloads
-                        .iter_mut()
-                        .find(|x| x.game_name_id == id)
-                        .unwrap()
-                        .state = DownloadState::Downloading {
-                        progress_percentage: percent,
+                let download = Download::new(&request);
+                match self.downloads.iter_mut().find(|x| x.id == download.id) {
+                    Some(existing) => {
+                        if matches!(
+                            existing.state,
+                            DownloadState::Errored(_)
+                                | DownloadState::Finished
+                                | DownloadState::Paused { .. }
+                        ) {
+                            existing.state = DownloadState::Queued;
+                        }
                     }
+                    None => self.downloads.push(download),
                 }
-                DownloadProgress::Finished { release } => {
-                    if let Err(_) = blackboard.config.update_install_state(
-                        &release.game_name_id,
-                        &release.version,
-                        &release.channel_name,
-                        Installed,
-                    ) {
-                        blackboard.screen =
-                            Screen::Error("failed to update config install state".to_string());
+            }
+            Message::DownloadProgressing((id, Ok(progress))) => {
+                let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) else {
+                    error!("got progress for unknown download {}", id);
+                    return Task::none();
+                };
+                // Progress still in flight when a download was paused is dropped
+                if !matches!(
+                    download.state,
+                    DownloadState::Downloading { .. } | DownloadState::Extracting
+                ) {
+                    info!("ignoring progress of {} while {:?}", id, download.state);
+                    return Task::none();
+                }
+                match progress {
+                    DownloadProgress::Downloading { percent } => {
+                        download.state = DownloadState::Downloading {
+                            progress_percentage: percent,
+                        };
+                        self.resumable.insert(id);
                     }
-                    blackboard.update_selected_game();
-                    if blackboard.selected_version.is_none() {
-                        blackboard.selected_version = Some(release.version);
+                    DownloadProgress::Extracting => download.state = DownloadState::Extracting,
+                    DownloadProgress::Finished { release } => {
+                        download.state = DownloadState::CarryingOver;
+                        self.start_queued_downloads(blackboard.config.max_parallel_downloads);
+                        return Self::carry_over(release, blackboard);
                     }
-                    blackboard.config.save().expect("failed to save config!");
-
-                    blackboard.screen = Screen::Main;
-                    self.downloads.retain(|x| x.game_name_id != id);
-
-                    let game = blackboard.selected_game.as_mut().unwrap();
-                    if game.app_link.is_some() {
-                        return Task::none();
+                }
+            }
+            Message::CarriedOver((release, result)) => {
+                match result {
+                    Ok(0) => {}
+                    Ok(copied) => info!("carried over {} files", copied),
+                    Err(e) => {
+                        error!("failed to carry over files: {}", e);
+                        blackboard.status_message =
+                            Some(format!("failed to carry over files: {}", e));
                     }
-
-                    #[cfg(windows)]
-                    match Self::create_windows_start_menu_entry(&game.name_id, &game.name) {
-                        Ok(path) => {
-                            game.app_link = Some(path);
-                            blackboard.config.save().expect("failed to save config!");
-                        }
-                        Err(e) => {
-                            blackboard.screen = Screen::Error(format!(
-                                "failed to create windows start menu entry: {}",
-                                e
-                            ));
-                            return Task::none();
-                        }
+                }
+                let id = Download::build_id(
+                    &release.game_name_id,
+                    &release.channel_name,
+                    &release.version,
+                );
+                if let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) {
+                    download.state = DownloadState::Finished;
+                }
+                self.resumable.remove(&id);
+                let task = self.handle_finished(release, blackboard);
+                self.start_queued_downloads(blackboard.config.max_parallel_downloads);
+                return task;
+            }
+            Message::DownloadProgressing((id, Err(error))) => {
+                // a partial file that doesn't match the checksum is removed
+                if let DownloadError::ChecksumMismatch { .. } = error {
+                    self.resumable.remove(&id);
+                }
+                if let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) {
+                    if matches!(
+                        download.state,
+                        DownloadState::Downloading { .. } | DownloadState::Extracting
+                    ) {
+                        download.state = DownloadState::Errored(error)
                     }
-
-                    #[cfg(unix)]
-                    if let Err(e) = Self::create_linux_desktop_entry(&game.name_id, &game.name) {
-                        error!("failed to create linux desktop entry: {}", e);
+                }
+            }
+            Message::RetryDownload(id) => {
+                if let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) {
+                    download.state = DownloadState::Queued;
+                }
+            }
+            Message::PauseDownload(id) => {
+                if let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) {
+                    if let DownloadState::Downloading {
+                        progress_percentage,
+                    } = download.state
+                    {
+                        download.state = DownloadState::Paused {
+                            progress_percentage,
+                        };
                     }
                 }
-            },
-            Message::DownloadProgressing((id, Err(error))) => {
+            }
+            Message::ResumeDownload(id) => {
+                if let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) {
+                    if let DownloadState::Paused { .. } = download.state {
+                        download.state = DownloadState::Queued;
+                    }
+                }
+            }
+            Message::CancelDownload(id) => {
+                let cancellable = |x: &Download| {
+                    x.id == id
+                        && !matches!(
+                            x.state,
+                            DownloadState::Extracting | DownloadState::CarryingOver
+                        )
+                };
+                if let Some(index) = self.downloads.iter().position(cancellable) {
+                    let download = self.downloads.remove(index);
+                    self.resumable.remove(&download.id);
+                    self.start_queued_downloads(blackboard.config.max_parallel_downloads);
+                    return Task::future(remove_partial_download(download.partial_path()))
+                        .discard();
+                }
+            }
+            Message::RemoveDownload(id) => {
+                self.downloads.retain(|x| x.id != id);
+            }
+            Message::ClearFinishedDownloads => {
                 self.downloads
-                    .iter_mut()
-                    .find(|x| x.game_name_id == id)
-                    .unwrap()
-                    .state = DownloadState::Errored(error)
+                    .retain(|x| !matches!(x.state, DownloadState::Finished));
             }
-            Message::CloseDownloadError(id) => {
-                self.downloads.retain(|x| x.game_name_id != id);
-                blackboard.screen = Screen::Main;
+            Message::PartialDownloadsFound(ids) => self.resumable = ids,
+            Message::MaxParallelDownloadsChanged(max) => {
+                blackboard.config.max_parallel_downloads = max;
+                blackboard.config.save().expect("failed to save config!");
             }
             _ => {
                 error!("invalid download state!")
             }
         }
+        self.start_queued_downloads(blackboard.config.max_parallel_downloads);
         Task::none()
     }
 }
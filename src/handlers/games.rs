@@ -1,27 +1,196 @@
 use crate::blackboard::Blackboard;
-use crate::client_config::ReleaseState;
-use crate::handlers::download::DownloadRequest;
+use crate::client_config::{Game, LaunchOptions, Release, ReleaseState};
+use crate::handlers::download::{DownloadMessageHandler, DownloadRequest, DownloadState};
 use crate::handlers::MessageHandler;
 use crate::messages::Message;
-use crate::{utils, view_utils};
+use crate::{utils, view_utils, Screen};
+use chrono::Local;
 use iced::widget::{button, column, text, vertical_space};
-use iced::widget::{pick_list, row, scrollable, Container};
-use iced::{Center, Element, Fill, Task};
+use iced::widget::{pick_list, row, scrollable, text_input, Column, Container, Row};
+use iced::{Center, Color, Element, Fill, Task};
 use log::error;
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 
 #[derive(Default)]
-pub struct GamesMessageHandler;
+pub struct GamesMessageHandler {
+    pending_uninstall: Option<UninstallTarget>,
+    tab: GameTab,
+    settings_editor: GameSettingsEditor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GameTab {
+    #[default]
+    Releases,
+    History,
+    Settings,
+}
+
+impl GameTab {
+    fn title(&self) -> &'static str {
+        match self {
+            GameTab::Releases => "Releases",
+            GameTab::History => "History",
+            GameTab::Settings => "Settings",
+        }
+    }
+}
+
+/// Text input state of the settings tab, parsed into [`LaunchOptions`] and the other
+/// game settings on save
+#[derive(Default)]
+struct GameSettingsEditor {
+    args: String,
+    env: String,
+    working_dir: String,
+    prefix: String,
+    default_prefix: String,
+    save_paths: String,
+    carry_over: String,
+    error: Option<String>,
+}
+
+impl GameSettingsEditor {
+    fn from_game(game: &Game, default_prefix: Option<&str>) -> Self {
+        let options = &game.launch_options;
+        let env: Vec<String> = options
+            .env
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        GameSettingsEditor {
+            args: utils::join_args(&options.args),
+            env: utils::join_args(&env),
+            working_dir: options.working_dir.clone().unwrap_or_default(),
+            prefix: options.prefix.clone().unwrap_or_default(),
+            default_prefix: default_prefix.unwrap_or_default().to_string(),
+            save_paths: utils::join_args(&game.save_paths),
+            carry_over: utils::join_args(&game.carry_over),
+            error: None,
+        }
+    }
+
+    fn parse(&self) -> Result<LaunchOptions, String> {
+        let env = utils::split_args(&self.env)?
+            .into_iter()
+            .map(|x| match x.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+                _ => Err(format!("{} is not a KEY=VALUE pair", x)),
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()?;
+        let working_dir = self.working_dir.trim();
+        Ok(LaunchOptions {
+            args: utils::split_args(&self.args)?,
+            env,
+            working_dir: (!working_dir.is_empty()).then(|| working_dir.to_string()),
+            prefix: Self::parse_prefix(&self.prefix)?,
+        })
+    }
+
+    fn parse_prefix(prefix: &str) -> Result<Option<String>, String> {
+        let prefix = prefix.trim();
+        if prefix.is_empty() {
+            return Ok(None);
+        }
+        utils::validate_launch_prefix(prefix)?;
+        Ok(Some(prefix.to_string()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum UninstallTarget {
+    Release(Release),
+    Game,
+}
+
+const MAX_HISTORY_SESSIONS: usize = 20;
+
+/// Option for the "keep installed" picker, `None` keeps every release
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeepInstalled(Option<usize>);
+
+impl KeepInstalled {
+    fn options() -> Vec<KeepInstalled> {
+        [None, Some(1), Some(2), Some(3), Some(5)]
+            .into_iter()
+            .map(KeepInstalled)
+            .collect()
+    }
+}
+
+impl Display for KeepInstalled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            None => write!(f, "keep all"),
+            Some(keep) => write!(f, "keep {}", keep),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UninstallResult {
+    game_name_id: String,
+    removed: Vec<Release>,
+    pub(crate) error: Option<String>,
+}
+
+/// Deletes the version directories of `releases`, stopping at the first failure.
+pub(crate) async fn remove_release_dirs(
+    games_dir: String,
+    game_name_id: String,
+    releases: Vec<Release>,
+) -> UninstallResult {
+    let mut removed = vec![];
+    for release in releases {
+        let release_dir = utils::get_exe_path(
+            &games_dir,
+            &game_name_id,
+            &release.channel_name,
+            &release.version,
+        );
+        match tokio::fs::remove_dir_all(&release_dir).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return UninstallResult {
+                    game_name_id,
+                    removed,
+                    error: Some(format!("failed to remove {}: {}", release_dir.display(), e)),
+                };
+            }
+        }
+        // Clean up the channel and game dirs once they are empty
+        if let Some(channel_dir) = release_dir.parent() {
+            let _ = tokio::fs::remove_dir(channel_dir).await;
+            if let Some(game_dir) = channel_dir.parent() {
+                let _ = tokio::fs::remove_dir(game_dir).await;
+            }
+        }
+        removed.push(release);
+    }
+    UninstallResult {
+        game_name_id,
+        removed,
+        error: None,
+    }
+}
 
 enum OptionButtonTypes {
     Play,
     Fetch,
     Update,
     Install,
+    Resume,
+    InProgress,
 }
 
 impl GamesMessageHandler {
-    pub fn view<'a>(&self, blackboard: &'a Blackboard) -> Element<'a, Message> {
+    pub fn view<'a>(
+        &self,
+        blackboard: &'a Blackboard,
+        downloads: &DownloadMessageHandler,
+    ) -> Element<'a, Message> {
         let games = blackboard.config.get_account_games();
         let game_count = games.len();
         let content = match &blackboard.selected_game {
@@ -60,9 +229,31 @@ impl GamesMessageHandler {
                 let latest_release =
                     utils::newest_release_by_state(&game.releases, Some(channel), None);
 
+                let has_partial_download = latest_release.as_ref().is_some_and(|x| {
+                    downloads.has_partial_download(&game.name_id, &x.channel_name, &x.version)
+                });
+
+                let pending_download = latest_release
+                    .as_ref()
+                    .and_then(|x| {
+                        downloads.get_download(&game.name_id, &x.channel_name, &x.version)
+                    })
+                    .and_then(|x| match &x.state {
+                        DownloadState::Queued => Some(format!(
+                            "Queued #{}",
+                            downloads.queue_position(&x.id).unwrap_or_default()
+                        )),
+                        DownloadState::Downloading {
+                            progress_percentage,
+                        } => Some(format!("Downloading {:.0}%", progress_percentage)),
+                        DownloadState::Extracting => Some("Installing".to_string()),
+                        _ => None,
+                    });
+
                 let option_button_type = match newest_installed {
                     None => match latest_release {
                         None => OptionButtonTypes::Fetch,
+                        Some(_) if has_partial_download => OptionButtonTypes::Resume,
                         Some(_) => OptionButtonTypes::Install,
                     },
                     Some(ref release) => match latest_release {
@@ -70,22 +261,28 @@ impl GamesMessageHandler {
                         Some(ref latest) if latest.version == release.version => {
                             OptionButtonTypes::Play
                         }
+                        Some(_) if has_partial_download => OptionButtonTypes::Resume,
                         Some(_) => OptionButtonTypes::Update,
                     },
                 };
+                let option_button_type = match option_button_type {
+                    OptionButtonTypes::Play | OptionButtonTypes::Fetch => option_button_type,
+                    _ if pending_download.is_some() => OptionButtonTypes::InProgress,
+                    _ => option_button_type,
+                };
 
                 let mk_btn = |name, msg| button(text(name).center()).on_press(msg);
 
                 let option_button = match option_button_type {
                     OptionButtonTypes::Play => mk_btn(
                         "Play",
-                        Message::Run(newest_installed.as_ref().unwrap().clone()),
+                        Message::Run(game.clone(), newest_installed.as_ref().unwrap().clone()),
                     ),
                     OptionButtonTypes::Fetch => mk_btn("Fetch releases", Message::FetchGames),
                     OptionButtonTypes::Update => mk_btn(
                         "Update",
                         Message::Download(DownloadRequest::build(
-                            &latest_release.as_ref().unwrap(),
+                            latest_release.as_ref().unwrap(),
                             game,
                             &blackboard.config,
                         )),
@@ -93,11 +290,23 @@ impl GamesMessageHandler {
                     OptionButtonTypes::Install => mk_btn(
                         "Install",
                         Message::Download(DownloadRequest::build(
-                            &latest_release.as_ref().unwrap(),
+                            latest_release.as_ref().unwrap(),
                             game,
                             &blackboard.config,
                         )),
                     ),
+                    OptionButtonTypes::Resume => mk_btn(
+                        "Resume",
+                        Message::Download(DownloadRequest::build(
+                            latest_release.as_ref().unwrap(),
+                            game,
+                            &blackboard.config,
+                        )),
+                    ),
+                    OptionButtonTypes::InProgress => {
+                        button(text(pending_download.unwrap_or_default()).center())
+                            .on_press(Message::GoToScreen(Screen::Downloads))
+                    }
                 };
                 let option_button = match option_button_type {
                     OptionButtonTypes::Play => Some(option_button),
@@ -161,6 +370,8 @@ impl GamesMessageHandler {
                     .spacing(20)
                     .width(300);
 
+                let uninstall_row = self.uninstall_row(game, blackboard);
+
                 let mut versions: Vec<(String, String)> = versions.into_iter().collect();
                 versions.sort_by(|(_, x), (_, y)| y.cmp(x));
 
@@ -172,15 +383,41 @@ impl GamesMessageHandler {
                     })
                     .spacing(10);
 
+                let playtime = match game.last_played() {
+                    None => "Never played".to_string(),
+                    Some(last_played) => format!(
+                        "Played {}, last played {}",
+                        utils::format_duration(game.total_playtime_secs()),
+                        last_played.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                    ),
+                };
+
+                let list: Element<Message> = match self.tab {
+                    GameTab::Releases => versions.into(),
+                    GameTab::History => Self::history_column(game).into(),
+                    GameTab::Settings => self.settings_column().into(),
+                };
+                let tabs = [GameTab::Releases, GameTab::History, GameTab::Settings]
+                    .into_iter()
+                    .filter(|x| x != &self.tab)
+                    .fold(row![text(self.tab.title()).size(20)], |r, tab| {
+                        r.push(
+                            button(text(tab.title().to_lowercase()).size(12).center())
+                                .on_press(Message::SelectGameTab(tab)),
+                        )
+                    });
+
                 let c = Container::new(
                     column![
                         text(game.name.to_string())
                             .size(32)
                             .width(450)
                             .align_x(Center),
+                        text(playtime).size(12),
                         vertical_space().height(3),
                         column![buttons].align_x(Center),
-                        vertical_space().height(20),
+                        uninstall_row,
+                        vertical_space().height(10),
                         text("Description").size(20),
                         vertical_space().height(2),
                         text(game.description.to_string())
@@ -188,19 +425,257 @@ impl GamesMessageHandler {
                             .width(450)
                             .align_x(Center),
                         vertical_space().height(15),
-                        text("Releases").size(20),
+                        tabs.spacing(10).align_y(Center),
                         vertical_space().height(2),
                     ]
                     .align_x(Center)
                     .width(Fill),
                 )
                 .width(Fill)
-                .height(210);
+                .height(260);
 
-                Container::new(column![c, scrollable(versions).width(400)])
+                Container::new(column![c, scrollable(list).width(400)])
             }
         };
-        view_utils::container_with_top_bar_and_side_view(content, blackboard)
+        view_utils::container_with_top_bar_and_side_view(
+            content,
+            blackboard,
+            downloads.pending_count(),
+        )
+    }
+}
+
+impl GamesMessageHandler {
+    fn settings_column<'a>(&self) -> Column<'a, Message> {
+        let mk_input = |label, placeholder, value: &str, on_input: fn(String) -> Message| {
+            row![
+                text(label).size(14).width(110),
+                text_input(placeholder, value)
+                    .on_input(on_input)
+                    .size(14)
+                    .width(250),
+            ]
+            .align_y(Center)
+        };
+        column![
+            mk_input(
+                "arguments",
+                "--windowed --log-level=debug",
+                &self.settings_editor.args,
+                Message::LaunchArgsChanged
+            ),
+            mk_input(
+                "environment",
+                "RUST_LOG=debug",
+                &self.settings_editor.env,
+                Message::LaunchEnvChanged
+            ),
+            mk_input(
+                "working dir",
+                "release directory",
+                &self.settings_editor.working_dir,
+                Message::LaunchWorkingDirChanged
+            ),
+            mk_input(
+                "prefix",
+                "gamemoderun {exe}",
+                &self.settings_editor.prefix,
+                Message::LaunchPrefixChanged
+            ),
+            mk_input(
+                "default prefix",
+                "used by all games without a prefix",
+                &self.settings_editor.default_prefix,
+                Message::DefaultLaunchPrefixChanged
+            ),
+            mk_input(
+                "save paths",
+                "saves ~/.config/game/settings.ini",
+                &self.settings_editor.save_paths,
+                Message::SavePathsChanged
+            ),
+            mk_input(
+                "carry over",
+                "settings.ini mods/ **/*.cfg",
+                &self.settings_editor.carry_over,
+                Message::CarryOverChanged
+            ),
+        ]
+        .push_maybe(
+            self.settings_editor
+                .error
+                .as_ref()
+                .map(|x| text(x.to_string()).color(Color::from_rgb(0.8, 0.4, 0.4))),
+        )
+        .push(button(text("save").center()).on_press(Message::SaveGameSettings))
+        .spacing(10)
+    }
+
+    fn history_column<'a>(game: &Game) -> Column<'a, Message> {
+        if game.sessions.is_empty() {
+            return column![text("No sessions yet").size(14)];
+        }
+        let by_release = game
+            .playtime_by_release()
+            .into_iter()
+            .fold(column![], |c, (channel, version, secs)| {
+                c.push(
+                    text(format!(
+                        "{} {}: {}",
+                        channel,
+                        version,
+                        utils::format_duration(secs)
+                    ))
+                    .size(14),
+                )
+            })
+            .spacing(4);
+
+        let recent_sessions = game
+            .sessions
+            .iter()
+            .rev()
+            .take(MAX_HISTORY_SESSIONS)
+            .fold(column![], |c, session| {
+                let exit = session
+                    .exit_code
+                    .map_or("no exit code".to_string(), |x| format!("exit {}", x));
+                let log_button = session.log_file.as_ref().filter(|x| x.exists()).map(|x| {
+                    button(text("log").size(10).center())
+                        .on_press(Message::ShowLogs(Some(x.clone())))
+                });
+                c.push(
+                    row![text(format!(
+                        "{}  {} {}  {}  {}",
+                        session
+                            .started_at
+                            .with_timezone(&Local)
+                            .format("%Y-%m-%d %H:%M"),
+                        session.channel_name,
+                        session.version,
+                        utils::format_duration(session.duration_secs),
+                        exit
+                    ))
+                    .size(12)]
+                    .push_maybe(log_button)
+                    .spacing(10)
+                    .align_y(Center),
+                )
+            })
+            .spacing(4);
+
+        column![
+            by_release,
+            vertical_space().height(10),
+            text("Recent sessions").size(16),
+            recent_sessions
+        ]
+        .spacing(4)
+    }
+
+    fn uninstall_row<'a>(&self, game: &Game, blackboard: &Blackboard) -> Row<'a, Message> {
+        let mk_btn = |name, msg| button(text(name).size(12).center()).on_press_maybe(msg);
+        if let Some(target) = &self.pending_uninstall {
+            let question = match target {
+                UninstallTarget::Release(release) => {
+                    format!("Uninstall {} {}?", release.channel_name, release.version)
+                }
+                UninstallTarget::Game => format!("Uninstall all versions of {}?", game.name),
+            };
+            return row![
+                text(question).size(14),
+                mk_btn("yes", Some(Message::ConfirmUninstall)),
+                mk_btn("no", Some(Message::CancelUninstall)),
+            ]
+            .spacing(10)
+            .align_y(Center);
+        }
+
+        let selected_release = game.releases.iter().find(|x| {
+            x.state == ReleaseState::Installed
+                && blackboard.selected_channel.as_ref() == Some(&x.channel_name)
+                && blackboard.selected_version.as_ref() == Some(&x.version)
+        });
+        // files of running games and downloads can't be removed
+        let removable_release = selected_release
+            .filter(|x| !blackboard.release_busy(&game.name_id, &x.channel_name, &x.version));
+        let any_installed = game
+            .releases
+            .iter()
+            .any(|x| x.state == ReleaseState::Installed);
+        let game_removable = any_installed && !blackboard.game_busy(&game.name_id);
+        let pin_label = match selected_release.is_some_and(|x| x.pinned) {
+            true => "unpin",
+            false => "pin",
+        };
+        let keep_installed = pick_list(
+            KeepInstalled::options(),
+            Some(KeepInstalled(game.keep_installed)),
+            Message::KeepInstalledChanged,
+        )
+        .text_size(12);
+        row![
+            mk_btn(
+                pin_label,
+                selected_release.map(|x| Message::TogglePinned(x.clone())),
+            ),
+            mk_btn(
+                "uninstall version",
+                removable_release.map(|x| Message::Uninstall(UninstallTarget::Release(x.clone()))),
+            ),
+            mk_btn(
+                "uninstall game",
+                game_removable.then_some(Message::Uninstall(UninstallTarget::Game)),
+            ),
+            keep_installed,
+        ]
+        .spacing(10)
+        .align_y(Center)
+    }
+
+    fn handle_uninstalled(&mut self, result: UninstallResult, blackboard: &mut Blackboard) {
+        for release in result.removed.iter() {
+            if let Err(e) = blackboard.config.update_install_state(
+                &result.game_name_id,
+                &release.version,
+                &release.channel_name,
+                ReleaseState::NotInstalled,
+            ) {
+                error!("failed to update install state: {}", e);
+            }
+        }
+        blackboard.update_selected_game();
+
+        let games = blackboard.config.get_account_games();
+        if let Some(game) = games.iter().find(|x| x.name_id == result.game_name_id) {
+            let is_selected_game = blackboard
+                .selected_game
+                .as_ref()
+                .is_some_and(|x| x.name_id == game.name_id);
+            if is_selected_game {
+                blackboard.selected_version = utils::newest_release_by_state(
+                    &game.releases,
+                    blackboard.selected_channel.as_deref(),
+                    Some(ReleaseState::Installed),
+                )
+                .map(|x| x.version);
+            }
+
+            let any_installed = game
+                .releases
+                .iter()
+                .any(|x| x.state == ReleaseState::Installed);
+            if let (false, Some(app_link)) = (any_installed, &game.app_link) {
+                if let Err(e) = std::fs::remove_file(app_link) {
+                    error!("failed to remove app link {}: {}", app_link.display(), e);
+                }
+                blackboard.config.set_app_link(&game.name_id, None);
+            }
+        }
+
+        if let Some(e) = result.error {
+            blackboard.screen = Screen::Error(e);
+        }
     }
 }
 
@@ -208,6 +683,11 @@ impl MessageHandler for GamesMessageHandler {
     fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
         match message {
             Message::SelectGame(game) => {
+                self.pending_uninstall = None;
+                self.settings_editor = GameSettingsEditor::from_game(
+                    &game,
+                    blackboard.config.launch_prefix.as_deref(),
+                );
                 blackboard.selected_channel = match game.selected_channel.as_ref() {
                     None => game.releases.first().map(|x| x.channel_name.to_string()),
                     Some(channel) => Some(channel.to_string()),
@@ -220,15 +700,116 @@ impl MessageHandler for GamesMessageHandler {
                     .filter(|x| x.state == ReleaseState::Installed)
                     .map(|x| x.version.to_string())
                     .collect();
-                versions_installed.sort_by(|x, y| y.cmp(&x));
+                versions_installed.sort_by(|x, y| y.cmp(x));
                 blackboard.selected_version = versions_installed.first().map(|x| x.to_string());
 
                 blackboard.selected_game = Some(game);
             }
 
-            Message::Run(release) => {
-                let game = blackboard.selected_game.as_ref().unwrap();
-                blackboard.run_release(&game.clone(), &release)
+            Message::Uninstall(target) => self.pending_uninstall = Some(target),
+            Message::CancelUninstall => self.pending_uninstall = None,
+            Message::ConfirmUninstall => {
+                let (Some(target), Some(game)) =
+                    (self.pending_uninstall.take(), &blackboard.selected_game)
+                else {
+                    return Task::none();
+                };
+                let busy = match &target {
+                    UninstallTarget::Release(x) => {
+                        blackboard.release_busy(&game.name_id, &x.channel_name, &x.version)
+                    }
+                    UninstallTarget::Game => blackboard.game_busy(&game.name_id),
+                };
+                if busy {
+                    blackboard.status_message = Some(format!(
+                        "stop {} and its downloads before uninstalling",
+                        game.name
+                    ));
+                    return Task::none();
+                }
+                let releases = match target {
+                    UninstallTarget::Release(release) => vec![release],
+                    UninstallTarget::Game => game
+                        .releases
+                        .iter()
+                        .filter(|x| x.state == ReleaseState::Installed)
+                        .cloned()
+                        .collect(),
+                };
+                return Task::perform(
+                    remove_release_dirs(
+                        blackboard.config.get_games_dir(),
+                        game.name_id.to_string(),
+                        releases,
+                    ),
+                    Message::ReleasesUninstalled,
+                );
+            }
+            Message::ReleasesUninstalled(result) => self.handle_uninstalled(result, blackboard),
+            Message::SelectGameTab(tab) => {
+                if let (GameTab::Settings, Some(game)) = (tab, &blackboard.selected_game) {
+                    self.settings_editor = GameSettingsEditor::from_game(
+                        game,
+                        blackboard.config.launch_prefix.as_deref(),
+                    );
+                }
+                self.tab = tab;
+            }
+            Message::LaunchArgsChanged(s) => self.settings_editor.args = s,
+            Message::LaunchEnvChanged(s) => self.settings_editor.env = s,
+            Message::LaunchWorkingDirChanged(s) => self.settings_editor.working_dir = s,
+            Message::LaunchPrefixChanged(s) => self.settings_editor.prefix = s,
+            Message::SavePathsChanged(s) => self.settings_editor.save_paths = s,
+            Message::CarryOverChanged(s) => self.settings_editor.carry_over = s,
+            Message::DefaultLaunchPrefixChanged(s) => self.settings_editor.default_prefix = s,
+            Message::SaveGameSettings => match self.settings_editor.parse().and_then(|x| {
+                let default_prefix =
+                    GameSettingsEditor::parse_prefix(&self.settings_editor.default_prefix)?;
+                let save_paths = utils::split_args(&self.settings_editor.save_paths)?;
+                let carry_over = utils::split_args(&self.settings_editor.carry_over)?;
+                Ok((x, default_prefix, save_paths, carry_over))
+            }) {
+                Ok((options, default_prefix, save_paths, carry_over)) => {
+                    blackboard.config.launch_prefix = default_prefix;
+                    let game_name_id = blackboard
+                        .selected_game
+                        .as_ref()
+                        .unwrap()
+                        .name_id
+                        .to_string();
+                    blackboard.config.set_launch_options(&game_name_id, options);
+                    blackboard.config.set_save_paths(&game_name_id, save_paths);
+                    blackboard.config.set_carry_over(&game_name_id, carry_over);
+                    blackboard.update_selected_game();
+                    self.settings_editor.error = None;
+                    blackboard.status_message = Some("saved settings".to_string());
+                }
+                Err(e) => self.settings_editor.error = Some(e),
+            },
+            Message::TogglePinned(release) => {
+                let game_name_id = blackboard
+                    .selected_game
+                    .as_ref()
+                    .unwrap()
+                    .name_id
+                    .to_string();
+                blackboard.config.set_pinned(
+                    &game_name_id,
+                    &release.version,
+                    &release.channel_name,
+                    !release.pinned,
+                );
+                blackboard.update_selected_game();
+            }
+            Message::KeepInstalledChanged(KeepInstalled(keep)) => {
+                let game_name_id = blackboard
+                    .selected_game
+                    .as_ref()
+                    .unwrap()
+                    .name_id
+                    .to_string();
+                blackboard.config.set_keep_installed(&game_name_id, keep);
+                blackboard.update_selected_game();
             }
             _ => {
                 error!("Unexpected state!")
@@ -0,0 +1,1310 @@
+use crate::blackboard::Blackboard;
+use crate::client_config::{DropsAccountConfig, Game, Release, ReleaseState};
+use crate::handlers::games::remove_release_dirs;
+use crate::handlers::MessageHandler;
+use crate::messages::Message;
+use crate::{utils, view_utils, Screen};
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, Stream};
+use iced::widget::{
+    button, column, horizontal_space, progress_bar, row, scrollable, text, vertical_space, Column,
+};
+use iced::{Center, Color, Element, Subscription, Task};
+use iced_futures::futures::channel::mpsc::Sender;
+use iced_futures::stream::try_channel;
+use log::{error, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Moving the installed releases of an account to another games dir
+#[derive(Debug, Clone)]
+pub struct LibraryMove {
+    account_id: Uuid,
+    from: String,
+    to: String,
+    releases: Vec<(String, Release)>,
+    app_links: Vec<(String, PathBuf)>,
+    /// Games whose partial downloads move along, so paused downloads can resume
+    games: Vec<String>,
+    /// Games that other accounts also have in the old games dir, their releases are
+    /// copied and left in place
+    shared_games: Vec<String>,
+}
+
+impl LibraryMove {
+    pub fn build(account: &DropsAccountConfig, to: &str, shared_games: Vec<String>) -> LibraryMove {
+        LibraryMove {
+            account_id: account.id,
+            from: account.games_dir.to_string(),
+            to: to.to_string(),
+            releases: account
+                .games
+                .iter()
+                .flat_map(|game| {
+                    game.releases
+                        .iter()
+                        .filter(|x| x.state == ReleaseState::Installed)
+                        .map(|x| (game.name_id.to_string(), x.clone()))
+                })
+                .collect(),
+            app_links: account
+                .games
+                .iter()
+                .filter_map(|x| Some((x.name_id.to_string(), x.app_link.clone()?)))
+                .collect(),
+            games: account
+                .games
+                .iter()
+                .map(|x| x.name_id.to_string())
+                .filter(|x| !shared_games.contains(x))
+                .collect(),
+            shared_games,
+        }
+    }
+
+    /// Leftovers of interrupted installs of the games in the old library, relative to
+    /// the games dir
+    async fn install_leftovers(&self) -> Result<Vec<PathBuf>, String> {
+        let (from, games) = (self.from.to_string(), self.games.clone());
+        unblock(move || {
+            let mut leftovers = vec![];
+            for game_name_id in &games {
+                let game_dir = utils::get_download_dir(&from, game_name_id);
+                let channels = match fs::read_dir(&game_dir) {
+                    Ok(x) => x,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                };
+                for channel in channels {
+                    for entry in fs::read_dir(channel?.path())? {
+                        let path = entry?.path();
+                        let is_leftover = path
+                            .file_name()
+                            .is_some_and(|x| utils::is_install_leftover(&x.to_string_lossy()));
+                        if let (true, Ok(relative)) = (is_leftover, path.strip_prefix(&from)) {
+                            leftovers.push(relative.to_path_buf());
+                        }
+                    }
+                }
+            }
+            Ok(leftovers)
+        })
+        .await
+    }
+
+    fn is_shared(&self, game_name_id: &str) -> bool {
+        self.shared_games.iter().any(|x| x == game_name_id)
+    }
+
+    fn release_dir(&self, games_dir: &str, game_name_id: &str, release: &Release) -> PathBuf {
+        utils::get_exe_path(
+            games_dir,
+            game_name_id,
+            &release.channel_name,
+            &release.version,
+        )
+    }
+
+    /// Moves every release dir, renaming where possible and copying otherwise, then
+    /// checks the result and points app links at the new library. Everything is undone
+    /// when a step fails. Sources of copied releases are only removed once the config
+    /// points at the new games dir, see [`remove_old_library`].
+    fn run(&self) -> impl Stream<Item = Result<MoveProgress, String>> {
+        let library_move = self.clone();
+        try_channel(1, move |mut output| async move {
+            let mut journal = MoveJournal::default();
+            match library_move.transfer(&mut output, &mut journal).await {
+                Ok(moved) => {
+                    let _ = output.send(MoveProgress::Moved(Box::new(moved))).await;
+                    Ok(())
+                }
+                Err(e) => {
+                    error!("failed to move library, rolling back: {}", e);
+                    journal.roll_back().await;
+                    Err(e)
+                }
+            }
+        })
+    }
+
+    async fn transfer(
+        &self,
+        output: &mut Sender<MoveProgress>,
+        journal: &mut MoveJournal,
+    ) -> Result<MovedLibrary, String> {
+        let mut sizes = vec![];
+        for (game_name_id, release) in &self.releases {
+            let target = self.release_dir(&self.to, game_name_id, release);
+            if tokio::fs::try_exists(&target).await.unwrap_or(true) {
+                return Err(format!("{} already exists", target.display()));
+            }
+            let source = self.release_dir(&self.from, game_name_id, release);
+            sizes.push(
+                unblock(move || dir_size(&source))
+                    .await
+                    .map_err(|e| format!("failed to read {}: {}", game_name_id, e))?,
+            );
+        }
+        // paused downloads continue from their partial file in the new library, half
+        // extracted releases are removed with the old library
+        let (mut partial_downloads, mut extract_leftovers) = (vec![], vec![]);
+        for relative in self.install_leftovers().await? {
+            let source = Path::new(&self.from).join(&relative);
+            let metadata = tokio::fs::metadata(&source)
+                .await
+                .map_err(|e| format!("failed to read {}: {}", source.display(), e))?;
+            match metadata.is_file() {
+                true => {
+                    let target = Path::new(&self.to).join(&relative);
+                    partial_downloads.push((source, target, metadata.len()));
+                }
+                false => extract_leftovers.push(source),
+            }
+        }
+        let mut total_bytes: u64 = sizes.iter().sum();
+        total_bytes += partial_downloads.iter().map(|x| x.2).sum::<u64>();
+
+        let mut done_bytes = 0;
+        for ((game_name_id, release), size) in self.releases.iter().zip(sizes) {
+            let source = self.release_dir(&self.from, game_name_id, release);
+            let target = self.release_dir(&self.to, game_name_id, release);
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+            }
+            info!("moving {} to {}", source.display(), target.display());
+            // a rename is instant, but only works within the same file system
+            if !self.is_shared(game_name_id) && tokio::fs::rename(&source, &target).await.is_ok() {
+                journal.renamed.push((source, target));
+                done_bytes += size;
+                let _ = output
+                    .send(MoveProgress::Transferring {
+                        done_bytes,
+                        total_bytes,
+                    })
+                    .await;
+                continue;
+            }
+            journal.copied.push(target.clone());
+            let (dirs, files) = unblock({
+                let source = source.clone();
+                move || list_tree(&source)
+            })
+            .await?;
+            // games may expect empty dirs like a saves dir to be there
+            for dir in dirs.iter().chain([&PathBuf::new()]) {
+                tokio::fs::create_dir_all(target.join(dir))
+                    .await
+                    .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+            }
+            for file in &files {
+                copy_file(&source.join(file), &target.join(file), |x| {
+                    done_bytes += x;
+                    let _ = output.try_send(MoveProgress::Transferring {
+                        done_bytes,
+                        total_bytes,
+                    });
+                })
+                .await
+                .map_err(|e| format!("failed to copy {}: {}", file.display(), e))?;
+            }
+        }
+        for (source, target, size) in &partial_downloads {
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+            }
+            info!("moving {} to {}", source.display(), target.display());
+            if tokio::fs::rename(source, target).await.is_ok() {
+                journal.renamed.push((source.clone(), target.clone()));
+                done_bytes += size;
+                continue;
+            }
+            journal.copied_files.push(target.clone());
+            copy_file(source, target, |x| {
+                done_bytes += x;
+                let _ = output.try_send(MoveProgress::Transferring {
+                    done_bytes,
+                    total_bytes,
+                });
+            })
+            .await
+            .map_err(|e| format!("failed to copy {}: {}", source.display(), e))?;
+        }
+
+        for (i, (game_name_id, release)) in self.releases.iter().enumerate() {
+            let _ = output
+                .send(MoveProgress::Verifying {
+                    done: i,
+                    total: self.releases.len(),
+                })
+                .await;
+            let source = self.release_dir(&self.from, game_name_id, release);
+            let target = self.release_dir(&self.to, game_name_id, release);
+            let executable = target.join(&release.executable_path);
+            if !tokio::fs::try_exists(executable).await.unwrap_or(false) {
+                return Err(format!(
+                    "{} {} is missing its executable after the move",
+                    game_name_id, release.version
+                ));
+            }
+            if journal.copied.contains(&target) {
+                unblock(move || verify_copy(&source, &target))
+                    .await
+                    .map_err(|e| {
+                        format!(
+                            "copy of {} {} differs: {}",
+                            game_name_id, release.version, e
+                        )
+                    })?;
+            }
+        }
+
+        let mut app_links = vec![];
+        for (game_name_id, link) in &self.app_links {
+            let new_link = self
+                .fix_app_link(link, journal)
+                .await
+                .map_err(|e| format!("failed to update app link {}: {}", link.display(), e))?;
+            app_links.push((game_name_id.to_string(), new_link));
+        }
+
+        Ok(MovedLibrary {
+            library_move: self.clone(),
+            app_links,
+            moved_links: journal.moved_links.clone(),
+            install_leftovers: partial_downloads
+                .into_iter()
+                .map(|x| x.0)
+                .chain(extract_leftovers)
+                .collect(),
+        })
+    }
+
+    /// App links start the client with the game as argument, they only mention the
+    /// library when edited by hand. Links kept inside the library are moved along.
+    async fn fix_app_link(
+        &self,
+        link: &Path,
+        journal: &mut MoveJournal,
+    ) -> std::io::Result<PathBuf> {
+        let from = utils::get_game_path(&self.from, "");
+        let to = utils::get_game_path(&self.to, "");
+        let new_link = match link.strip_prefix(&from) {
+            Ok(relative) => to.join(relative),
+            Err(_) => link.to_path_buf(),
+        };
+        let Ok(contents) = tokio::fs::read(link).await else {
+            // already moved along with its release dir, or removed by the user
+            return Ok(
+                match tokio::fs::try_exists(&new_link).await.unwrap_or(false) {
+                    true => new_link,
+                    false => link.to_path_buf(),
+                },
+            );
+        };
+        let (from, to) = (from.to_string_lossy(), to.to_string_lossy());
+        // windows shortcuts are binary, they point at the client executable
+        let updated = match String::from_utf8(contents.clone()) {
+            Ok(text) if text.contains(from.as_ref()) => {
+                text.replace(from.as_ref(), to.as_ref()).into_bytes()
+            }
+            _ => contents.clone(),
+        };
+        if new_link == link && updated == contents {
+            return Ok(new_link);
+        }
+        if let Some(parent) = new_link.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        journal
+            .links
+            .push((new_link.clone(), link.to_path_buf(), contents));
+        tokio::fs::write(&new_link, updated).await?;
+        if new_link != link {
+            journal.moved_links.push(link.to_path_buf());
+        }
+        Ok(new_link)
+    }
+}
+
+/// What was done so far, to undo it when a later step fails
+#[derive(Default)]
+struct MoveJournal {
+    renamed: Vec<(PathBuf, PathBuf)>,
+    copied: Vec<PathBuf>,
+    copied_files: Vec<PathBuf>,
+    /// Written link, original link and its original contents
+    links: Vec<(PathBuf, PathBuf, Vec<u8>)>,
+    moved_links: Vec<PathBuf>,
+}
+
+impl MoveJournal {
+    async fn roll_back(self) {
+        for (written, original, contents) in self.links.into_iter().rev() {
+            let result = match written == original {
+                true => tokio::fs::write(&original, contents).await,
+                false => tokio::fs::remove_file(&written).await,
+            };
+            if let Err(e) = result {
+                error!("failed to restore app link {}: {}", original.display(), e);
+            }
+        }
+        for target in &self.copied {
+            if let Err(e) = tokio::fs::remove_dir_all(target).await {
+                error!("failed to remove copy {}: {}", target.display(), e);
+            }
+        }
+        for (source, target) in self.renamed.iter().rev() {
+            if let Err(e) = tokio::fs::rename(target, source).await {
+                error!(
+                    "failed to move {} back to {}: {}",
+                    target.display(),
+                    source.display(),
+                    e
+                );
+            }
+        }
+        for target in &self.copied_files {
+            if let Err(e) = tokio::fs::remove_file(target).await {
+                error!("failed to remove copy {}: {}", target.display(), e);
+            }
+        }
+        // the channel and game dirs created in the new library, once they are empty
+        let targets = self
+            .copied
+            .iter()
+            .chain(&self.copied_files)
+            .chain(self.renamed.iter().map(|x| &x.1));
+        for channel_dir in targets.filter_map(|x| x.parent()) {
+            let _ = tokio::fs::remove_dir(channel_dir).await;
+            if let Some(game_dir) = channel_dir.parent() {
+                let _ = tokio::fs::remove_dir(game_dir).await;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MoveProgress {
+    Transferring { done_bytes: u64, total_bytes: u64 },
+    Verifying { done: usize, total: usize },
+    Moved(Box<MovedLibrary>),
+}
+
+/// A library that was moved but whose old location hasn't been cleaned up yet
+#[derive(Debug, Clone)]
+pub struct MovedLibrary {
+    library_move: LibraryMove,
+    app_links: Vec<(String, PathBuf)>,
+    moved_links: Vec<PathBuf>,
+    /// Partial downloads in the old library, already gone unless they were copied, and
+    /// half extracted releases
+    install_leftovers: Vec<PathBuf>,
+}
+
+impl MovedLibrary {
+    /// Old and new games dir
+    pub(crate) fn games_dirs(&self) -> (&str, &str) {
+        (&self.library_move.from, &self.library_move.to)
+    }
+}
+
+/// Removes what is left of the old library once the config points at the new one
+async fn remove_old_library(moved: MovedLibrary) -> Result<(), String> {
+    let library_move = moved.library_move;
+    for link in moved.moved_links {
+        let _ = tokio::fs::remove_file(link).await;
+    }
+    let mut errors = vec![];
+    for leftover in moved.install_leftovers {
+        let result = match tokio::fs::metadata(&leftover).await {
+            Ok(x) if x.is_dir() => tokio::fs::remove_dir_all(&leftover).await,
+            Ok(_) => tokio::fs::remove_file(&leftover).await,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            errors.push(format!("{}: {}", leftover.display(), e));
+        }
+    }
+    for (game_name_id, release) in library_move.releases.clone() {
+        if library_move.is_shared(&game_name_id) {
+            info!(
+                "keeping {} in the old library for other accounts",
+                game_name_id
+            );
+            continue;
+        }
+        let result =
+            remove_release_dirs(library_move.from.to_string(), game_name_id, vec![release]).await;
+        errors.extend(result.error);
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join(", ")),
+    }
+}
+
+/// Runs blocking file system work on a thread of its own, walking and hashing a library
+/// would stall the other tasks of the runtime otherwise
+async fn unblock<T, E>(work: impl FnOnce() -> Result<T, E> + Send + 'static) -> Result<T, String>
+where
+    T: Send + 'static,
+    E: ToString + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Relative paths of the directories and of the files and symlinks below `dir`
+fn list_tree(dir: &Path) -> std::io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    fn visit(
+        dir: &Path,
+        relative: &Path,
+        tree: &mut (Vec<PathBuf>, Vec<PathBuf>),
+    ) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                visit(&entry.path(), &relative, tree)?;
+                tree.0.push(relative);
+            } else {
+                tree.1.push(relative);
+            }
+        }
+        Ok(())
+    }
+    let mut tree = (vec![], vec![]);
+    visit(dir, Path::new(""), &mut tree)?;
+    Ok(tree)
+}
+
+fn list_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    Ok(list_tree(dir)?.1)
+}
+
+fn dir_size(dir: &Path) -> std::io::Result<u64> {
+    list_files(dir)?
+        .iter()
+        .map(|x| Ok(dir.join(x).symlink_metadata()?.len()))
+        .sum()
+}
+
+/// Copies a file in chunks, reporting the size of each chunk
+async fn copy_file(
+    source: &Path,
+    target: &Path,
+    mut progress: impl FnMut(u64),
+) -> std::io::Result<()> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let metadata = tokio::fs::symlink_metadata(source).await?;
+    if metadata.is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(tokio::fs::read_link(source).await?, target)?;
+        #[cfg(not(unix))]
+        log::warn!("skipping symlink {}", source.display());
+        progress(metadata.len());
+        return Ok(());
+    }
+    let mut reader = tokio::fs::File::open(source).await?;
+    let mut writer = tokio::fs::File::create(target).await?;
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).await?;
+        progress(read as u64);
+    }
+    writer.flush().await?;
+    writer.set_permissions(metadata.permissions()).await?;
+    writer.sync_all().await
+}
+
+/// Compares every file of a copied release with its source
+fn verify_copy(source: &Path, target: &Path) -> Result<(), String> {
+    let files = list_files(source).map_err(|e| e.to_string())?;
+    for file in files {
+        let (source_file, target_file) = (source.join(&file), target.join(&file));
+        let source_meta = source_file.symlink_metadata().map_err(|e| e.to_string())?;
+        if source_meta.is_symlink() {
+            continue;
+        }
+        let matches = utils::sha256_file(&source_file).map_err(|e| e.to_string())?
+            == utils::sha256_file(&target_file)
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+        if !matches {
+            return Err(format!("{} has different contents", file.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Result of walking the games dir of an account
+#[derive(Debug, Clone)]
+pub struct LibraryScan {
+    account_id: Uuid,
+    /// `(game, channel, version)` of every release dir that has its executable
+    installed: Vec<(String, String, String)>,
+    unknown: Vec<UnknownDir>,
+}
+
+/// A directory of the library that doesn't belong to an installed release
+#[derive(Debug, Clone)]
+pub struct UnknownDir {
+    path: PathBuf,
+    name: String,
+    kind: UnknownDirKind,
+}
+
+#[derive(Debug, Clone)]
+enum UnknownDirKind {
+    Game,
+    Channel,
+    /// A version the server doesn't list (anymore). It can be added to the game when the
+    /// executable of another release of the game is found in it.
+    Version {
+        game_name_id: String,
+        release: Option<Release>,
+    },
+    /// The dir of a known release without its executable, e.g. from an interrupted install
+    Incomplete,
+    /// Partial download or extraction of an interrupted install
+    Leftover,
+}
+
+impl UnknownDir {
+    fn description(&self) -> &'static str {
+        match &self.kind {
+            UnknownDirKind::Game => "unknown game",
+            UnknownDirKind::Channel => "unknown channel",
+            UnknownDirKind::Version { release: None, .. } => "unknown version",
+            UnknownDirKind::Version { .. } => "version not listed by the server",
+            UnknownDirKind::Incomplete => "incomplete install",
+            UnknownDirKind::Leftover => "leftover of an interrupted install",
+        }
+    }
+}
+
+fn sub_dirs(dir: &Path) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut dirs = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            dirs.push((
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            ));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// A release for a version dir the server doesn't know, using the executable of another
+/// release of the game when the dir contains it
+fn sideload_release(game: &Game, channel_name: &str, version: &str, dir: &Path) -> Option<Release> {
+    let mut releases: Vec<&Release> = game.releases.iter().collect();
+    // releases of the same channel first, they most likely share the executable
+    releases.sort_by_key(|x| {
+        (
+            x.channel_name != channel_name,
+            std::cmp::Reverse(x.release_date),
+        )
+    });
+    let executable_path = releases
+        .iter()
+        .map(|x| &x.executable_path)
+        .find(|x| dir.join(x).is_file())?;
+    let modified = dir.metadata().and_then(|x| x.modified()).ok()?;
+    Some(Release {
+        channel_name: channel_name.to_string(),
+        version: version.to_string(),
+        description: "sideloaded".to_string(),
+        state: ReleaseState::Installed,
+        release_date: DateTime::<Utc>::from(modified),
+        executable_path: executable_path.to_string(),
+        size_bytes: dir_size(dir).unwrap_or(0),
+        pinned: false,
+    })
+}
+
+/// Matches the `{game}/{channel}/{version}` dirs of the library of an account to the
+/// releases of its games. Dirs of `shared_games`, the games of other accounts using the
+/// same games dir, are left alone.
+fn scan_library(
+    account: &DropsAccountConfig,
+    shared_games: &[String],
+) -> Result<LibraryScan, String> {
+    let root = utils::get_game_path(&account.games_dir, "");
+    let mut scan = LibraryScan {
+        account_id: account.id,
+        installed: vec![],
+        unknown: vec![],
+    };
+    let read_error = |path: &Path, e: std::io::Error| format!("{}: {}", path.display(), e);
+    let game_dirs = match sub_dirs(&root) {
+        Ok(x) => x,
+        // nothing was installed yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(read_error(&root, e)),
+    };
+    for (game_name_id, game_dir) in game_dirs {
+        let Some(game) = account.games.iter().find(|x| x.name_id == game_name_id) else {
+            if !shared_games.contains(&game_name_id) {
+                scan.unknown.push(UnknownDir {
+                    path: game_dir,
+                    name: game_name_id,
+                    kind: UnknownDirKind::Game,
+                });
+            }
+            continue;
+        };
+        let channels = sub_dirs(&game_dir).map_err(|e| read_error(&game_dir, e))?;
+        for (channel_name, channel_dir) in channels {
+            let name = format!("{}/{}", game_name_id, channel_name);
+            if !game.releases.iter().any(|x| x.channel_name == channel_name) {
+                scan.unknown.push(UnknownDir {
+                    path: channel_dir,
+                    name,
+                    kind: UnknownDirKind::Channel,
+                });
+                continue;
+            }
+            let versions = sub_dirs(&channel_dir).map_err(|e| read_error(&channel_dir, e))?;
+            for (version, version_dir) in versions {
+                let name = format!("{}/{}", name, version);
+                let release = game
+                    .releases
+                    .iter()
+                    .find(|x| x.channel_name == channel_name && x.version == version);
+                let kind = match release {
+                    _ if utils::is_install_leftover(&version) => UnknownDirKind::Leftover,
+                    Some(x) if version_dir.join(&x.executable_path).is_file() => {
+                        scan.installed.push((
+                            game_name_id.to_string(),
+                            channel_name.to_string(),
+                            version,
+                        ));
+                        continue;
+                    }
+                    Some(_) => UnknownDirKind::Incomplete,
+                    None => UnknownDirKind::Version {
+                        game_name_id: game_name_id.to_string(),
+                        release: sideload_release(game, &channel_name, &version, &version_dir),
+                    },
+                };
+                scan.unknown.push(UnknownDir {
+                    path: version_dir,
+                    name,
+                    kind,
+                });
+            }
+        }
+    }
+    Ok(scan)
+}
+
+#[derive(Debug, Clone)]
+enum LibraryState {
+    Idle,
+    Moving(LibraryMove, MoveProgressView),
+    MoveFailed(String),
+    Scanning,
+    Scanned {
+        found: usize,
+        newly_installed: usize,
+        unknown: Vec<UnknownDir>,
+        pending_remove: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum MoveProgressView {
+    Transferring { percent: f32 },
+    Verifying { percent: f32 },
+    Finishing,
+}
+
+pub struct LibraryMessageHandler {
+    state: LibraryState,
+}
+
+impl Default for LibraryMessageHandler {
+    fn default() -> Self {
+        LibraryMessageHandler {
+            state: LibraryState::Idle,
+        }
+    }
+}
+
+impl LibraryMessageHandler {
+    /// The account whose library is being moved, its games can't be started or
+    /// downloaded until the config points at the new games dir
+    pub(crate) fn moving_account(&self) -> Option<Uuid> {
+        match &self.state {
+            LibraryState::Moving(library_move, _) => Some(library_move.account_id),
+            _ => None,
+        }
+    }
+
+    pub fn view(&self, _: &Blackboard) -> Element<'_, Message> {
+        let content = match &self.state {
+            LibraryState::Idle => column![],
+            LibraryState::Moving(library_move, progress) => {
+                let (stage, percent) = match progress {
+                    MoveProgressView::Transferring { percent } => ("moving games", *percent),
+                    MoveProgressView::Verifying { percent } => ("verifying", *percent),
+                    MoveProgressView::Finishing => ("cleaning up", 100.0),
+                };
+                column![
+                    text(stage),
+                    progress_bar(0.0..=100.0, percent).width(300).height(10),
+                    text(format!("{} -> {}", library_move.from, library_move.to)).size(12),
+                ]
+            }
+            LibraryState::MoveFailed(e) => column![
+                text(format!("The library was not moved: {}", e))
+                    .color(Color::from_rgb(0.8, 0.4, 0.4))
+                    .width(400),
+                vertical_space().height(20),
+                button(text("close").center()).on_press(Message::ShowAccounts),
+            ],
+            LibraryState::Scanning => column![text("scanning...")],
+            LibraryState::Scanned {
+                found,
+                newly_installed,
+                unknown,
+                pending_remove,
+            } => Self::scan_column(*found, *newly_installed, unknown, pending_remove.as_ref()),
+        };
+        let title = match &self.state {
+            LibraryState::Moving(..) | LibraryState::MoveFailed(_) => "Moving library",
+            _ => "Library",
+        };
+        view_utils::container_with_title(title.to_string(), content.spacing(10).align_x(Center))
+    }
+
+    fn scan_column<'a>(
+        found: usize,
+        newly_installed: usize,
+        unknown: &'a [UnknownDir],
+        pending_remove: Option<&PathBuf>,
+    ) -> Column<'a, Message> {
+        let mk_btn = |name, msg| button(text(name).size(12).center()).on_press_maybe(msg);
+        let unknown_dirs = unknown.iter().fold(column![].spacing(5), |c, dir| {
+            let actions = match pending_remove == Some(&dir.path) {
+                true => row![
+                    text("Remove?").size(12),
+                    mk_btn("yes", Some(Message::ConfirmRemoveUnknownDir)),
+                    mk_btn("no", Some(Message::CancelRemoveUnknownDir)),
+                ],
+                false => {
+                    let can_add = matches!(
+                        &dir.kind,
+                        UnknownDirKind::Version {
+                            release: Some(_),
+                            ..
+                        }
+                    );
+                    row![]
+                        .push_maybe(can_add.then(|| {
+                            mk_btn("add", Some(Message::SideloadRelease(dir.path.clone())))
+                        }))
+                        .push(mk_btn(
+                            "remove",
+                            Some(Message::RemoveUnknownDir(dir.path.clone())),
+                        ))
+                }
+            };
+            c.push(
+                row![
+                    column![text(&dir.name).size(14), text(dir.description()).size(12)],
+                    horizontal_space(),
+                    actions.spacing(10).align_y(Center),
+                ]
+                .align_y(Center),
+            )
+        });
+        column![text(format!(
+            "{} installed releases, {} newly found",
+            found, newly_installed
+        ))]
+        .push_maybe(
+            (!unknown.is_empty()).then(|| text("Not part of an installed release").size(16)),
+        )
+        .push(scrollable(unknown_dirs.padding(5)).height(200).width(450))
+        .push(button(text("close").center()).on_press(Message::ShowAccounts))
+    }
+
+    fn unknown_dirs(&mut self) -> Option<(&mut Vec<UnknownDir>, &mut Option<PathBuf>)> {
+        match &mut self.state {
+            LibraryState::Scanned {
+                unknown,
+                pending_remove,
+                ..
+            } => Some((unknown, pending_remove)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn subscription(&self) -> Subscription<Message> {
+        match &self.state {
+            LibraryState::Moving(library_move, progress)
+                if !matches!(progress, MoveProgressView::Finishing) =>
+            {
+                Subscription::run_with_id(library_move.account_id, library_move.run())
+                    .map(Message::LibraryMoveProgressed)
+            }
+            _ => Subscription::none(),
+        }
+    }
+}
+
+impl MessageHandler for LibraryMessageHandler {
+    fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
+        match message {
+            Message::MoveLibrary(library_move) => {
+                if let Some(id) = self.moving_account() {
+                    error!("library of {} is already being moved", id);
+                    return Task::none();
+                }
+                info!(
+                    "moving library from {} to {}",
+                    library_move.from, library_move.to
+                );
+                self.state = LibraryState::Moving(
+                    library_move,
+                    MoveProgressView::Transferring { percent: 0.0 },
+                );
+                blackboard.screen = Screen::Library;
+            }
+            Message::LibraryMoveProgressed(Ok(progress)) => {
+                let LibraryState::Moving(_, view) = &mut self.state else {
+                    return Task::none();
+                };
+                match progress {
+                    MoveProgress::Transferring {
+                        done_bytes,
+                        total_bytes,
+                    } => {
+                        *view = MoveProgressView::Transferring {
+                            percent: 100.0 * done_bytes as f32 / total_bytes.max(1) as f32,
+                        }
+                    }
+                    MoveProgress::Verifying { done, total } => {
+                        *view = MoveProgressView::Verifying {
+                            percent: 100.0 * done as f32 / total.max(1) as f32,
+                        }
+                    }
+                    MoveProgress::Moved(moved) => {
+                        *view = MoveProgressView::Finishing;
+                        let library_move = &moved.library_move;
+                        blackboard.config.update_account_and_save(
+                            library_move.account_id,
+                            |account| {
+                                account.games_dir = library_move.to.to_string();
+                                for game in account.games.iter_mut() {
+                                    if let Some((_, link)) =
+                                        moved.app_links.iter().find(|x| x.0 == game.name_id)
+                                    {
+                                        game.app_link = Some(link.clone());
+                                    }
+                                }
+                                account.refresh_install_states();
+                            },
+                        );
+                        if library_move.account_id == blackboard.config.active_account {
+                            blackboard.update_selected_game();
+                        }
+                        return Task::perform(
+                            remove_old_library(*moved),
+                            Message::OldLibraryRemoved,
+                        );
+                    }
+                }
+            }
+            Message::LibraryMoveProgressed(Err(e)) => {
+                self.state = LibraryState::MoveFailed(e);
+            }
+            Message::OldLibraryRemoved(result) => {
+                self.state = LibraryState::Idle;
+                blackboard.status_message = Some(match result {
+                    Ok(()) => "library moved".to_string(),
+                    Err(e) => {
+                        error!("failed to clean up old library: {}", e);
+                        format!("library moved, but some old files are left: {}", e)
+                    }
+                });
+                return Task::done(Message::ShowAccounts);
+            }
+            Message::ScanLibrary => {
+                // a scan would see the half extracted releases of downloads
+                if blackboard.active_account_busy() {
+                    blackboard.status_message = Some(
+                        "stop running games and downloads before scanning the library".to_string(),
+                    );
+                    return Task::none();
+                }
+                let account = blackboard.config.get_active_account().unwrap();
+                let shared_games = blackboard.config.shared_games(&account);
+                self.state = LibraryState::Scanning;
+                blackboard.screen = Screen::Library;
+                return Task::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || scan_library(&account, &shared_games))
+                            .await
+                            .map_err(|e| e.to_string())
+                            .and_then(|x| x)
+                    },
+                    Message::LibraryScanned,
+                );
+            }
+            Message::LibraryScanned(Ok(scan)) => {
+                let mut newly_installed = 0;
+                // the scan belongs to the account it was started for, even if another one
+                // is active by now
+                blackboard
+                    .config
+                    .update_account_and_save(scan.account_id, |account| {
+                        for game in account.games.iter_mut() {
+                            for release in game.releases.iter_mut() {
+                                let found = scan.installed.iter().any(|(g, c, v)| {
+                                    g == &game.name_id
+                                        && c == &release.channel_name
+                                        && v == &release.version
+                                });
+                                if found && release.state != ReleaseState::Installed {
+                                    newly_installed += 1;
+                                }
+                                release.state = match found {
+                                    true => ReleaseState::Installed,
+                                    false => ReleaseState::NotInstalled,
+                                };
+                            }
+                        }
+                    });
+                if scan.account_id == blackboard.config.active_account {
+                    blackboard.update_selected_game();
+                }
+                info!(
+                    "library scan found {} releases, {} unknown dirs",
+                    scan.installed.len(),
+                    scan.unknown.len()
+                );
+                self.state = LibraryState::Scanned {
+                    found: scan.installed.len(),
+                    newly_installed,
+                    unknown: scan.unknown,
+                    pending_remove: None,
+                };
+            }
+            Message::LibraryScanned(Err(e)) => {
+                error!("failed to scan library: {}", e);
+                self.state = LibraryState::Idle;
+                blackboard.status_message = Some(format!("failed to scan library: {}", e));
+                return Task::done(Message::ShowAccounts);
+            }
+            Message::RemoveUnknownDir(path) => {
+                if let Some((_, pending_remove)) = self.unknown_dirs() {
+                    *pending_remove = Some(path);
+                }
+            }
+            Message::CancelRemoveUnknownDir => {
+                if let Some((_, pending_remove)) = self.unknown_dirs() {
+                    *pending_remove = None;
+                }
+            }
+            Message::ConfirmRemoveUnknownDir => {
+                let Some(path) = self.unknown_dirs().and_then(|x| x.1.take()) else {
+                    return Task::none();
+                };
+                info!("removing {}", path.display());
+                return Task::perform(
+                    async move {
+                        tokio::fs::remove_dir_all(&path)
+                            .await
+                            .map(|_| path.clone())
+                            .map_err(|e| format!("failed to remove {}: {}", path.display(), e))
+                    },
+                    Message::UnknownDirRemoved,
+                );
+            }
+            Message::UnknownDirRemoved(Ok(path)) => {
+                if let Some((unknown, _)) = self.unknown_dirs() {
+                    // dirs below the removed one are gone as well
+                    unknown.retain(|x| !x.path.starts_with(&path));
+                }
+            }
+            Message::UnknownDirRemoved(Err(e)) => {
+                error!("{}", e);
+                blackboard.status_message = Some(e);
+            }
+            Message::SideloadRelease(path) => {
+                let Some((unknown, _)) = self.unknown_dirs() else {
+                    return Task::none();
+                };
+                let Some(index) = unknown.iter().position(|x| x.path == path) else {
+                    return Task::none();
+                };
+                if let UnknownDirKind::Version {
+                    game_name_id,
+                    release: Some(release),
+                } = &unknown[index].kind
+                {
+                    info!(
+                        "adding {} {} {} from the library",
+                        game_name_id, release.channel_name, release.version
+                    );
+                    blackboard.config.add_release(game_name_id, release.clone());
+                    blackboard.update_selected_game();
+                    unknown.remove(index);
+                }
+            }
+            _ => error!("invalid library state message: {:?}", message),
+        }
+        Task::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn release(channel_name: &str, version: &str) -> Release {
+        Release {
+            channel_name: channel_name.to_string(),
+            version: version.to_string(),
+            description: String::new(),
+            state: ReleaseState::Installed,
+            release_date: Utc::now(),
+            executable_path: "game.exe".to_string(),
+            size_bytes: 0,
+            pinned: false,
+        }
+    }
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn failed_move_is_rolled_back() {
+        let root = tempfile::tempdir().unwrap();
+        let from = root.path().join("old").to_string_lossy().to_string();
+        let to = root.path().join("new").to_string_lossy().to_string();
+        let library_move = LibraryMove {
+            account_id: Uuid::new_v4(),
+            from: from.to_string(),
+            to: to.to_string(),
+            releases: vec![
+                ("moved".to_string(), release("stable", "1.0")),
+                ("shared".to_string(), release("stable", "2.0")),
+            ],
+            app_links: vec![],
+            games: vec!["moved".to_string()],
+            // shared games are copied, the copy of this one lacks its executable
+            shared_games: vec!["shared".to_string()],
+        };
+        let moved = utils::get_exe_path(&from, "moved", "stable", "1.0");
+        write(&moved.join("game.exe"), "moved");
+        let shared = utils::get_exe_path(&from, "shared", "stable", "2.0");
+        write(&shared.join("data/level.bin"), "level");
+        fs::create_dir_all(shared.join("saves")).unwrap();
+
+        let (mut output, _progress) = iced_futures::futures::channel::mpsc::channel(100);
+        let mut journal = MoveJournal::default();
+        let result = block_on(library_move.transfer(&mut output, &mut journal));
+        assert!(result.unwrap_err().contains("missing its executable"));
+        assert_eq!(journal.renamed.len(), 1);
+        assert_eq!(journal.copied.len(), 1);
+
+        block_on(journal.roll_back());
+        assert_eq!(fs::read_to_string(moved.join("game.exe")).unwrap(), "moved");
+        assert!(shared.join("data/level.bin").is_file());
+        assert!(shared.join("saves").is_dir());
+        // nothing is left in the new library but its root
+        let left: Vec<_> = fs::read_dir(utils::get_game_path(&to, ""))
+            .unwrap()
+            .collect();
+        assert!(left.is_empty());
+    }
+
+    #[test]
+    fn install_leftovers_are_moved_or_removed() {
+        let root = tempfile::tempdir().unwrap();
+        let from = root.path().join("old").to_string_lossy().to_string();
+        let to = root.path().join("new").to_string_lossy().to_string();
+        let library_move = LibraryMove {
+            account_id: Uuid::new_v4(),
+            from: from.to_string(),
+            to: to.to_string(),
+            releases: vec![("game".to_string(), release("stable", "1.0"))],
+            app_links: vec![],
+            games: vec!["game".to_string()],
+            shared_games: vec![],
+        };
+        write(
+            &utils::get_exe_path(&from, "game", "stable", "1.0").join("game.exe"),
+            "game",
+        );
+        let partial = utils::get_partial_download_path(&from, "game", "stable", "2.0");
+        write(&partial, "first bytes");
+        let extracting = utils::get_extract_path(&from, "game", "stable", "3.0");
+        write(&extracting.join("game.exe"), "half");
+
+        let (mut output, _progress) = iced_futures::futures::channel::mpsc::channel(100);
+        let moved = block_on(library_move.transfer(&mut output, &mut MoveJournal::default()));
+        block_on(remove_old_library(moved.unwrap())).unwrap();
+        let moved_partial = utils::get_partial_download_path(&to, "game", "stable", "2.0");
+        assert_eq!(fs::read_to_string(moved_partial).unwrap(), "first bytes");
+        assert!(!partial.exists());
+        assert!(!extracting.exists());
+        assert!(!utils::get_extract_path(&to, "game", "stable", "3.0").exists());
+    }
+
+    fn game(name_id: &str, releases: Vec<Release>) -> Game {
+        Game {
+            name_id: name_id.to_string(),
+            releases,
+            ..Default::default()
+        }
+    }
+
+    fn account(games_dir: &Path, games: Vec<Game>) -> DropsAccountConfig {
+        DropsAccountConfig {
+            id: Uuid::new_v4(),
+            name: None,
+            games_dir: games_dir.to_string_lossy().to_string(),
+            url: String::new(),
+            username: String::new(),
+            session_token: Default::default(),
+            games,
+        }
+    }
+
+    #[test]
+    fn scan_classifies_library_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let games_dir = root.path().to_string_lossy().to_string();
+        let account = account(
+            root.path(),
+            vec![game(
+                "space",
+                vec![release("stable", "1.0"), release("stable", "1.1")],
+            )],
+        );
+        let dir = |game, channel, version| utils::get_exe_path(&games_dir, game, channel, version);
+        write(&dir("space", "stable", "1.0").join("game.exe"), "");
+        write(&dir("space", "stable", "1.1").join("readme.txt"), "");
+        write(&dir("space", "stable", "0.9").join("game.exe"), "");
+        write(&dir("space", "beta", "2.0").join("game.exe"), "");
+        write(&dir("other", "stable", "1.0").join("game.exe"), "");
+        write(&dir("shared", "stable", "1.0").join("game.exe"), "");
+        // leftovers of interrupted installs, even with the executable in them
+        write(
+            &dir("space", "stable", "1.1.extracting").join("game.exe"),
+            "",
+        );
+        write(&dir("space", "stable", "1.0.part").join("game.exe"), "");
+
+        let scan = scan_library(&account, &["shared".to_string()]).unwrap();
+        assert_eq!(scan.account_id, account.id);
+        assert_eq!(
+            scan.installed,
+            vec![("space".to_string(), "stable".to_string(), "1.0".to_string())]
+        );
+        let unknown: Vec<(&str, &str)> = scan
+            .unknown
+            .iter()
+            .map(|x| (x.name.as_str(), x.description()))
+            .collect();
+        assert_eq!(
+            unknown,
+            vec![
+                ("other", "unknown game"),
+                ("space/beta", "unknown channel"),
+                ("space/stable/0.9", "version not listed by the server"),
+                (
+                    "space/stable/1.0.part",
+                    "leftover of an interrupted install"
+                ),
+                ("space/stable/1.1", "incomplete install"),
+                (
+                    "space/stable/1.1.extracting",
+                    "leftover of an interrupted install"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_of_missing_library_is_empty() {
+        let root = tempfile::tempdir().unwrap();
+        let account = account(&root.path().join("never created"), vec![]);
+        let scan = scan_library(&account, &[]).unwrap();
+        assert!(scan.installed.is_empty());
+        assert!(scan.unknown.is_empty());
+    }
+
+    #[test]
+    fn sideload_uses_executable_of_matching_release() {
+        let mut windows = release("stable", "1.0");
+        windows.executable_path = "win/game.exe".to_string();
+        let mut linux = release("beta", "1.0");
+        linux.executable_path = "linux/game".to_string();
+        let game = game("space", vec![windows, linux]);
+
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("linux/game"), "binary");
+        let release = sideload_release(&game, "stable", "0.9", dir.path()).unwrap();
+        assert_eq!(release.channel_name, "stable");
+        assert_eq!(release.version, "0.9");
+        assert_eq!(release.executable_path, "linux/game");
+        assert_eq!(release.size_bytes, 6);
+        assert_eq!(release.state, ReleaseState::Installed);
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(sideload_release(&game, "stable", "0.9", empty.path()).is_none());
+    }
+
+    #[test]
+    fn verify_copy_finds_differing_files() {
+        let source = tempfile::tempdir().unwrap();
+        let target = tempfile::tempdir().unwrap();
+        for dir in [&source, &target] {
+            write(&dir.path().join("game.exe"), "game");
+            write(&dir.path().join("data/level.bin"), "level 1");
+        }
+        assert_eq!(verify_copy(source.path(), target.path()), Ok(()));
+
+        write(&target.path().join("data/level.bin"), "level 2");
+        let error = verify_copy(source.path(), target.path()).unwrap_err();
+        assert!(error.contains("level.bin"));
+
+        fs::remove_file(target.path().join("data/level.bin")).unwrap();
+        assert!(verify_copy(source.path(), target.path()).is_err());
+    }
+}
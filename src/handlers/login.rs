@@ -1,4 +1,5 @@
 use crate::blackboard::Blackboard;
+use crate::client_config::DropsAccountConfig;
 use crate::handlers::MessageHandler;
 use crate::messages::Message;
 use crate::{tasks, view_utils, Screen};
@@ -9,6 +10,30 @@ use iced::widget::{
 use iced::{Color, Element, Task};
 use log::error;
 use secrecy::{ExposeSecret, SecretString};
+use std::fmt::{Display, Formatter};
+use uuid::Uuid;
+
+/// Entry of the account picker, accounts are told apart by id as several can share a server
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountOption {
+    id: Uuid,
+    name: String,
+}
+
+impl From<&DropsAccountConfig> for AccountOption {
+    fn from(account: &DropsAccountConfig) -> Self {
+        AccountOption {
+            id: account.id,
+            name: account.display_name(),
+        }
+    }
+}
+
+impl Display for AccountOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
 
 #[derive(Default)]
 pub struct LoginMessageHandler {
@@ -18,14 +43,15 @@ pub struct LoginMessageHandler {
 }
 
 impl LoginMessageHandler {
+    const BUSY_REASON: &'static str = "stop running games and downloads before switching accounts";
+
     pub(crate) fn set_username(&mut self, username: &str) {
         self.username_input = username.to_string();
     }
-    pub fn view(&self, blackboard: &Blackboard) -> Element<Message> {
+    pub fn view(&self, blackboard: &Blackboard) -> Element<'_, Message> {
         match blackboard.screen {
             Screen::Login => {
                 view_utils::container_with_title("drops".to_string(), self.login_column(blackboard))
-                    .into()
             }
             Screen::LoggingIn => Container::new(column![text("logging in")
                 .size(40)
@@ -35,27 +61,19 @@ impl LoginMessageHandler {
             _ => column![].into(),
         }
     }
-    fn login_column(&self, blackboard: &Blackboard) -> Column<Message> {
-        let options = blackboard
+    fn login_column(&self, blackboard: &Blackboard) -> Column<'_, Message> {
+        let options: Vec<AccountOption> = blackboard
             .config
             .accounts
             .iter()
-            .map(|x| x.url.to_string())
-            .collect::<Vec<String>>();
-
-        let active_one = blackboard.config.get_active_account();
-        let default = active_one.map(|account| {
-            blackboard
-                .config
-                .accounts
-                .iter()
-                .find(|x| x.id == account.id)
-                .unwrap()
-                .url
-                .to_string()
-        });
+            .map(AccountOption::from)
+            .collect();
+        let selected = blackboard
+            .config
+            .get_active_account()
+            .map(|x| AccountOption::from(&x));
 
-        let server_select = pick_list(options, default, Message::ServerChanged).width(250);
+        let account_select = pick_list(options, selected, Message::AccountChanged).width(250);
 
         let username_input: TextInput<Message> = text_input("Username", &self.username_input)
             .on_input(Message::UsernameChanged)
@@ -75,10 +93,14 @@ impl LoginMessageHandler {
             .padding(10)
             .width(200);
 
-        let new_server_button = button(text("new server").center())
+        let new_account_button = button(text("add account").center())
             .on_press(Message::GoToScreen(Screen::Wizard))
             .padding(5)
             .width(150);
+        let manage_accounts_button = button(text("manage accounts").center())
+            .on_press(Message::ShowAccounts)
+            .padding(5)
+            .width(150);
 
         let inputs = column![]
             .push_maybe(
@@ -86,7 +108,7 @@ impl LoginMessageHandler {
                     .clone()
                     .map(|x| text(x).color(Color::from_rgb(0.8, 0.4, 0.4))),
             )
-            .push(server_select)
+            .push(account_select)
             .push(username_input)
             .push(password_input)
             .spacing(10);
@@ -97,11 +119,15 @@ impl LoginMessageHandler {
             .push(vertical_space().height(5))
             .push(row![horizontal_space(), login_button, horizontal_space()])
             .push(vertical_space().height(10))
-            .push(row![
-                horizontal_space(),
-                new_server_button,
-                horizontal_space()
-            ])
+            .push(
+                row![
+                    horizontal_space(),
+                    new_account_button,
+                    manage_accounts_button,
+                    horizontal_space()
+                ]
+                .spacing(10),
+            )
             .push(vertical_space().height(50))
     }
 }
@@ -110,6 +136,33 @@ impl MessageHandler for LoginMessageHandler {
     fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
         match message {
             Message::Login => {
+                let account = blackboard.config.get_active_account().unwrap();
+                let is_other_user =
+                    !account.username.is_empty() && account.username != self.username_input;
+                if is_other_user
+                    && blackboard
+                        .config
+                        .find_account_on_active_server(&self.username_input)
+                        .is_none()
+                {
+                    // the games and installs of an account belong to its user
+                    self.error_reason = Some(format!(
+                        "{} belongs to {}, add an account to log in as {}",
+                        account.display_name(),
+                        account.username,
+                        self.username_input
+                    ));
+                    return Task::none();
+                }
+                let switches_account = blackboard
+                    .config
+                    .find_account_on_active_server(&self.username_input)
+                    .is_some_and(|x| x.id != account.id);
+                if switches_account && blackboard.active_account_busy() {
+                    self.error_reason = Some(Self::BUSY_REASON.to_string());
+                    return Task::none();
+                }
+                self.error_reason = None;
                 blackboard.screen = Screen::LoggingIn;
                 let url = blackboard.config.get_drops_url();
                 return tasks::perform_login(
@@ -121,6 +174,14 @@ impl MessageHandler for LoginMessageHandler {
 
             Message::LoggedInFinished(result) => match result {
                 Ok(token) => {
+                    // logging in as another user of the same server switches to their account
+                    if let Some(id) = blackboard
+                        .config
+                        .find_account_on_active_server(&self.username_input)
+                        .map(|x| x.id)
+                    {
+                        blackboard.config.set_active_account(id);
+                    }
                     blackboard
                         .config
                         .set_username_and_save(&self.username_input);
@@ -136,9 +197,16 @@ impl MessageHandler for LoginMessageHandler {
             },
             Message::UsernameChanged(s) => self.username_input = s,
             Message::PasswordChanged(s) => self.password_input = SecretString::new(s.into()),
-            Message::ServerChanged(s) => {
-                self.username_input.clear();
-                blackboard.config.set_active_account_by_url(s);
+            Message::AccountChanged(account) => {
+                if account.id != blackboard.config.active_account
+                    && blackboard.active_account_busy()
+                {
+                    self.error_reason = Some(Self::BUSY_REASON.to_string());
+                    return Task::none();
+                }
+                blackboard.config.set_active_account(account.id);
+                self.username_input = blackboard.config.get_username();
+                self.error_reason = None;
             }
             _ => {
                 error!("invalid login state message: {:?}", message)
@@ -0,0 +1,336 @@
+use crate::blackboard::Blackboard;
+use crate::client_config::get_config_dir;
+use crate::handlers::MessageHandler;
+use crate::messages::Message;
+use crate::Screen;
+use chrono::{DateTime, Local, Utc};
+use iced::widget::{
+    button, column, horizontal_space, row, scrollable, text, text_input, vertical_space,
+};
+use iced::{Center, Element, Fill, Task};
+use log::{error, info};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Session logs kept per game, the oldest ones are deleted when a game is launched
+const MAX_LOGS_PER_GAME: usize = 10;
+const MAX_LISTED_LOGS: usize = 20;
+/// Only the tail of a log is shown, rendering huge files would stall the UI
+const MAX_DISPLAYED_LOG_BYTES: u64 = 200 * 1024;
+/// A session log stops growing at this size, so a game spamming its output can't fill
+/// the disk
+const MAX_SESSION_LOG_BYTES: u64 = 20 * 1024 * 1024;
+
+pub fn get_logs_dir() -> PathBuf {
+    get_config_dir().join("logs")
+}
+
+pub fn session_log_path(
+    game_name_id: &str,
+    channel_name: &str,
+    version: &str,
+    started_at: DateTime<Utc>,
+) -> PathBuf {
+    get_logs_dir().join(game_name_id).join(format!(
+        "{}_{}_{}.log",
+        channel_name,
+        version,
+        started_at.format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Creates the log file for a new session and removes the oldest logs of the game
+pub fn create_session_log(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+        let mut logs = list_logs_in(dir)?;
+        for old_log in logs.drain(..).skip(MAX_LOGS_PER_GAME.saturating_sub(1)) {
+            info!("removing old session log {}", old_log.path.display());
+            let _ = fs::remove_file(old_log.path);
+        }
+    }
+    File::create(path)
+}
+
+/// The session log that stdout and stderr of a game are written to, capped at
+/// [`MAX_SESSION_LOG_BYTES`]
+pub struct SessionLogWriter {
+    file: File,
+    written: u64,
+    limit: u64,
+}
+
+impl SessionLogWriter {
+    pub fn new(file: File) -> Arc<Mutex<Self>> {
+        Self::with_limit(file, MAX_SESSION_LOG_BYTES)
+    }
+
+    fn with_limit(file: File, limit: u64) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(SessionLogWriter {
+            file,
+            written: 0,
+            limit,
+        }))
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.written >= self.limit {
+            return Ok(());
+        }
+        let allowed = data.len().min((self.limit - self.written) as usize);
+        self.file.write_all(&data[..allowed])?;
+        self.written += allowed as u64;
+        if self.written >= self.limit {
+            writeln!(self.file, "\n[log truncated at {} bytes]", self.limit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the output of a game into its session log on a background thread. Output past
+/// the size cap is still read and dropped, so the game never blocks on a full pipe.
+pub fn capture_output(mut source: impl Read + Send + 'static, log: Arc<Mutex<SessionLogWriter>>) {
+    std::thread::spawn(move || {
+        let mut buffer = [0; 8192];
+        loop {
+            let read = match source.read(&mut buffer) {
+                Ok(0) => return,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return,
+            };
+            let Ok(mut log) = log.lock() else {
+                return;
+            };
+            if let Err(e) = log.write(&buffer[..read]) {
+                error!("failed to write session log: {}", e);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionLog {
+    pub game_name_id: String,
+    pub path: PathBuf,
+    modified: SystemTime,
+}
+
+impl SessionLog {
+    fn title(&self) -> String {
+        let file_name = self
+            .path
+            .file_stem()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default();
+        format!("{} {}", self.game_name_id, file_name)
+    }
+}
+
+/// Logs in `dir`, newest first
+fn list_logs_in(dir: &Path) -> io::Result<Vec<SessionLog>> {
+    let game_name_id = dir
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut logs: Vec<SessionLog> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter(|x| x.path().extension().is_some_and(|x| x == "log"))
+        .filter_map(|x| {
+            Some(SessionLog {
+                game_name_id: game_name_id.to_string(),
+                path: x.path(),
+                modified: x.metadata().ok()?.modified().ok()?,
+            })
+        })
+        .collect();
+    logs.sort_by_key(|x| std::cmp::Reverse(x.modified));
+    Ok(logs)
+}
+
+fn list_recent_logs() -> Vec<SessionLog> {
+    let Ok(game_dirs) = fs::read_dir(get_logs_dir()) else {
+        return vec![];
+    };
+    let mut logs: Vec<SessionLog> = game_dirs
+        .filter_map(Result::ok)
+        .filter(|x| x.path().is_dir())
+        .filter_map(|x| list_logs_in(&x.path()).ok())
+        .flatten()
+        .collect();
+    logs.sort_by_key(|x| std::cmp::Reverse(x.modified));
+    logs.truncate(MAX_LISTED_LOGS);
+    logs
+}
+
+pub(crate) async fn read_log_tail(path: PathBuf) -> Result<String, String> {
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let len = file.metadata().await.map_err(|e| e.to_string())?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(MAX_DISPLAYED_LOG_BYTES)))
+        .await
+        .map_err(|e| e.to_string())?;
+    // the log of a running game may grow while it is read
+    let mut content = vec![];
+    file.take(MAX_DISPLAYED_LOG_BYTES)
+        .read_to_end(&mut content)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&content).to_string())
+}
+
+#[derive(Default)]
+pub struct LogsMessageHandler {
+    logs: Vec<SessionLog>,
+    selected: Option<PathBuf>,
+    content: String,
+    search_input: String,
+}
+
+impl LogsMessageHandler {
+    pub fn view(&self, _: &Blackboard) -> Element<'_, Message> {
+        let header = row![
+            button(text("back").center()).on_press(Message::GoToScreen(Screen::Main)),
+            horizontal_space(),
+            text("Logs").size(32),
+            horizontal_space(),
+        ]
+        .align_y(Center);
+
+        let log_list = self.logs.iter().fold(column![], |c, log| {
+            let is_selected = self.selected.as_ref() == Some(&log.path);
+            c.push(
+                button(text(log.title()).size(12))
+                    .width(Fill)
+                    .style(match is_selected {
+                        true => button::primary,
+                        false => button::secondary,
+                    })
+                    .on_press(Message::SelectLog(log.path.clone())),
+            )
+        });
+
+        let lines: String = match self.search_input.is_empty() {
+            true => self.content.to_string(),
+            false => self
+                .content
+                .lines()
+                .filter(|x| x.contains(&self.search_input))
+                .collect::<Vec<&str>>()
+                .join("\n"),
+        };
+
+        let modified = self
+            .logs
+            .iter()
+            .find(|x| Some(&x.path) == self.selected.as_ref())
+            .map(|x| {
+                DateTime::<Local>::from(x.modified)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            });
+        let content_header = row![
+            text_input("search", &self.search_input)
+                .on_input(Message::LogSearchChanged)
+                .size(12)
+                .width(150),
+            button(text("copy").size(12).center())
+                .on_press_maybe(self.selected.is_some().then_some(Message::CopyLog)),
+        ]
+        .push_maybe(modified.map(|x| text(x).size(12)))
+        .spacing(10)
+        .align_y(Center);
+
+        let content = match self.logs.is_empty() {
+            true => column![text("No session logs yet")],
+            false => column![
+                content_header,
+                scrollable(text(lines).size(11)).height(Fill).width(Fill)
+            ]
+            .spacing(10),
+        };
+
+        column![
+            header,
+            vertical_space().height(10),
+            row![
+                scrollable(log_list.spacing(5)).width(170).height(Fill),
+                content
+            ]
+            .spacing(10)
+        ]
+        .padding(20)
+        .width(Fill)
+        .height(Fill)
+        .into()
+    }
+}
+
+impl MessageHandler for LogsMessageHandler {
+    fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
+        match message {
+            Message::ShowLogs(path) => {
+                blackboard.screen = Screen::Logs;
+                self.logs = list_recent_logs();
+                let selected = path.or(self.logs.first().map(|x| x.path.clone()));
+                if let Some(selected) = selected {
+                    return Task::done(Message::SelectLog(selected));
+                }
+            }
+            Message::SelectLog(path) => {
+                self.selected = Some(path.clone());
+                self.content.clear();
+                return Task::perform(read_log_tail(path), Message::LogLoaded);
+            }
+            Message::LogLoaded(Ok(content)) => self.content = content,
+            Message::LogLoaded(Err(e)) => {
+                error!("failed to read log: {}", e);
+                self.content = format!("failed to read log: {}", e);
+            }
+            Message::LogSearchChanged(s) => self.search_input = s,
+            Message::CopyLog => return iced::clipboard::write(self.content.to_string()),
+            _ => error!("invalid logs state message: {:?}", message),
+        }
+        Task::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_log_stops_at_limit() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let log = SessionLogWriter::with_limit(file.reopen().unwrap(), 10);
+        let mut log = log.lock().unwrap();
+        log.write(b"0123456").unwrap();
+        log.write(b"789abc").unwrap();
+        log.write(b"def").unwrap();
+        let content = fs::read_to_string(file.path()).unwrap();
+        assert_eq!(content, "0123456789\n[log truncated at 10 bytes]\n");
+    }
+
+    #[test]
+    fn reads_only_the_tail() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"head\n").unwrap();
+        file.write_all(&vec![b'x'; MAX_DISPLAYED_LOG_BYTES as usize])
+            .unwrap();
+        let tail = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(read_log_tail(file.path().to_path_buf()))
+            .unwrap();
+        assert_eq!(tail.len() as u64, MAX_DISPLAYED_LOG_BYTES);
+        assert!(!tail.contains("head"));
+    }
+}
@@ -2,10 +2,16 @@ use crate::blackboard::Blackboard;
 use crate::messages::Message;
 use iced::Task;
 
+pub mod accounts;
 pub mod client_update;
+pub mod crash;
 pub mod download;
 pub mod games;
+pub mod library;
 pub mod login;
+pub mod logs;
+pub mod playing;
+pub mod saves;
 pub mod wizard;
 
 pub trait MessageHandler {
@@ -0,0 +1,568 @@
+use crate::blackboard::Blackboard;
+use crate::client_config::{Game, PlaySession, Release, ReleaseState};
+use crate::errors::LaunchError;
+use crate::handlers::crash::Crash;
+use crate::handlers::download::DownloadRequest;
+use crate::handlers::games::remove_release_dirs;
+use crate::handlers::logs;
+use crate::handlers::saves::SyncPoint;
+use crate::handlers::MessageHandler;
+use crate::messages::Message;
+use crate::{utils, view_utils, Screen};
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, Stream, StreamExt};
+use iced::widget::{button, column, row, text, vertical_space, Row};
+use iced::{Center, Element, Task};
+use iced_futures::stream::channel;
+use iced_futures::Subscription;
+use log::{error, info};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessesToUpdate, Signal, System};
+
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Time a game gets to exit on its own after being asked to stop before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct GameExit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+}
+
+impl GameExit {
+    /// Non-zero exit codes and signals count as crashes
+    pub fn is_crash(&self) -> bool {
+        self.signal.is_some() || self.code.is_some_and(|x| x != 0)
+    }
+
+    fn from_status(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+        GameExit {
+            code: status.code(),
+            signal,
+        }
+    }
+}
+
+impl Display for GameExit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exited with code {}", code),
+            (None, Some(signal)) => write!(f, "killed by signal {}", signal),
+            (None, None) => write!(f, "exited with unknown status"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    Started,
+    Exited(GameExit),
+    Failed(LaunchError),
+}
+
+/// Checks that the executable exists and, on unix, restores a missing executable bit,
+/// which is easily lost when a build is zipped on another platform.
+fn prepare_executable(path: &Path) -> Result<(), LaunchError> {
+    if !path.is_file() {
+        return Err(LaunchError::MissingExecutable(path.to_path_buf()));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let not_executable = |e: std::io::Error| LaunchError::NotExecutable {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        };
+        let mut permissions = std::fs::metadata(path)
+            .map_err(not_executable)?
+            .permissions();
+        if permissions.mode() & 0o111 == 0 {
+            info!("{} is missing the executable bit, fixing", path.display());
+            permissions.set_mode(permissions.mode() | 0o755);
+            std::fs::set_permissions(path, permissions).map_err(not_executable)?;
+        }
+    }
+    Ok(())
+}
+
+/// A process and everything it started, children after their parents. With a launch
+/// prefix the launched process is only the wrapper and the game runs as one of its
+/// descendants.
+fn process_tree(system: &System, pid: u32) -> Vec<Pid> {
+    let mut tree = vec![Pid::from_u32(pid)];
+    let mut i = 0;
+    while i < tree.len() {
+        let parent = tree[i];
+        let children: Vec<Pid> = system
+            .processes()
+            .iter()
+            .filter(|(pid, x)| x.parent() == Some(parent) && !tree.contains(pid))
+            .map(|(pid, _)| *pid)
+            .collect();
+        tree.extend(children);
+        i += 1;
+    }
+    tree
+}
+
+/// Sends `signal` to a process and all of its descendants, the descendants first. Returns
+/// whether the process itself got the signal.
+fn signal_process_tree(pid: u32, signal: Signal) -> bool {
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::All, true);
+    let mut signalled = false;
+    for x in process_tree(&system, pid).iter().rev() {
+        signalled = system
+            .process(*x)
+            .and_then(|x| x.kill_with(signal))
+            .unwrap_or(false);
+    }
+    signalled
+}
+
+/// Asks a process and the processes it started to exit on their own. Returns false if the
+/// platform has no way of asking nicely, they have to be killed then.
+fn request_exit(pid: u32) -> bool {
+    let requested = signal_process_tree(pid, Signal::Term);
+    info!("asked process {} to exit: {}", pid, requested);
+    requested
+}
+
+#[derive(Debug, Clone)]
+pub struct RunningGame {
+    pub(crate) id: String,
+    game: Game,
+    release: Release,
+    started_at: DateTime<Utc>,
+    log_path: PathBuf,
+    pub(crate) game_name: String,
+    pub(crate) game_name_id: String,
+    executable_dir: PathBuf,
+    executable_path: PathBuf,
+    launch_prefix: Option<String>,
+    /// Set from the UI, the stream owning the process polls it
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl RunningGame {
+    pub fn new(
+        game: &Game,
+        release: &Release,
+        games_dir: &str,
+        default_launch_prefix: Option<&str>,
+    ) -> Self {
+        let executable_dir = utils::get_exe_path(
+            games_dir,
+            &game.name_id,
+            &release.channel_name,
+            &release.version,
+        );
+        let executable_path = executable_dir.join(&release.executable_path);
+        let started_at = Utc::now();
+        Self {
+            id: Self::build_id(game, release),
+            game: game.clone(),
+            release: release.clone(),
+            started_at,
+            log_path: logs::session_log_path(
+                &game.name_id,
+                &release.channel_name,
+                &release.version,
+                started_at,
+            ),
+            game_name: game.name.to_string(),
+            game_name_id: game.name_id.to_string(),
+            executable_dir,
+            executable_path,
+            launch_prefix: game
+                .launch_options
+                .prefix
+                .as_deref()
+                .or(default_launch_prefix)
+                .map(|x| x.to_string()),
+            stop_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn build_id(game: &Game, release: &Release) -> String {
+        utils::release_id(&game.name_id, &release.channel_name, &release.version)
+    }
+
+    fn is_stopping(&self) -> bool {
+        self.stop_requested.load(Ordering::Relaxed)
+    }
+
+    /// Spawns the game and reports back when it exits. The process is owned by the stream,
+    /// so the UI thread never blocks on it.
+    fn run(&self) -> impl Stream<Item = GameEvent> {
+        let executable_dir = self.executable_dir.clone();
+        let executable_path = self.executable_path.clone();
+        let log_path = self.log_path.clone();
+        let launch_options = self.game.launch_options.clone();
+        let launch_prefix = self.launch_prefix.clone();
+        let stop_requested = self.stop_requested.clone();
+        channel(1, move |mut output| async move {
+            // stdout and stderr both go to the session log, testers attach it to bug reports
+            let session_log = match logs::create_session_log(&log_path) {
+                Ok(file) => Some(logs::SessionLogWriter::new(file)),
+                Err(e) => {
+                    error!("failed to create log {}: {}", log_path.display(), e);
+                    None
+                }
+            };
+            let output_to = || match session_log {
+                Some(_) => Stdio::piped(),
+                None => Stdio::inherit(),
+            };
+            let working_dir = match &launch_options.working_dir {
+                None => executable_dir.to_path_buf(),
+                Some(dir) => executable_dir.join(dir),
+            };
+            let command_line = match &launch_prefix {
+                None => Ok(vec![executable_path.to_string_lossy().to_string()]),
+                Some(prefix) => {
+                    utils::expand_launch_prefix(prefix, &executable_path, &executable_dir)
+                        .map_err(LaunchError::InvalidLaunchPrefix)
+                }
+            };
+            let spawned = prepare_executable(&executable_path)
+                .and(command_line)
+                .and_then(|x| {
+                    info!("launching {}", utils::join_args(&x));
+                    Command::new(&x[0])
+                        .args(&x[1..])
+                        .args(&launch_options.args)
+                        .current_dir(&working_dir)
+                        .envs(std::env::vars())
+                        .envs(launch_options.env.iter().cloned())
+                        .stdout(output_to())
+                        .stderr(output_to())
+                        .spawn()
+                        .map_err(|e| LaunchError::SpawnFailed {
+                            path: PathBuf::from(&x[0]),
+                            reason: e.to_string(),
+                        })
+                });
+            let mut child = match spawned {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = output.send(GameEvent::Failed(e)).await;
+                    return;
+                }
+            };
+            if let Some(session_log) = &session_log {
+                if let Some(stdout) = child.stdout.take() {
+                    logs::capture_output(stdout, session_log.clone());
+                }
+                if let Some(stderr) = child.stderr.take() {
+                    logs::capture_output(stderr, session_log.clone());
+                }
+            }
+            info!(
+                "started {} with pid {}",
+                executable_path.display(),
+                child.id()
+            );
+            let _ = output.send(GameEvent::Started).await;
+
+            let mut kill_at = None;
+            let event = loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break GameEvent::Exited(GameExit::from_status(status)),
+                    Ok(None) => {
+                        if stop_requested.load(Ordering::Relaxed) {
+                            match kill_at {
+                                None if request_exit(child.id()) => {
+                                    kill_at = Some(Instant::now() + STOP_TIMEOUT)
+                                }
+                                Some(deadline) if Instant::now() < deadline => {}
+                                _ => {
+                                    info!("killing {}", executable_path.display());
+                                    signal_process_tree(child.id(), Signal::Kill);
+                                    if let Err(e) = child.kill() {
+                                        error!("failed to kill game: {}", e);
+                                    }
+                                    kill_at = Some(Instant::now() + STOP_TIMEOUT);
+                                }
+                            }
+                        }
+                        tokio::time::sleep(EXIT_POLL_INTERVAL).await
+                    }
+                    Err(e) => {
+                        error!("failed to wait for game: {}", e);
+                        break GameEvent::Exited(GameExit {
+                            code: None,
+                            signal: None,
+                        });
+                    }
+                }
+            };
+            let _ = output.send(event).await;
+        })
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        let id = self.id.to_string();
+        Subscription::run_with_id(
+            id.to_string(),
+            self.run().map(move |event| (id.to_string(), event)),
+        )
+        .map(Message::GameEvent)
+    }
+}
+
+#[derive(Default)]
+pub struct PlayingMessageHandler {
+    pub(crate) running: Vec<RunningGame>,
+    /// Ids of games that are syncing their saves before they are launched
+    pub(crate) launching: Vec<String>,
+    failed_launch: Option<(Game, Release)>,
+}
+
+impl PlayingMessageHandler {
+    pub fn view(&self, blackboard: &Blackboard) -> Element<'_, Message> {
+        let name = match &blackboard.screen {
+            Screen::PlayingGame(name) => name.to_string(),
+            _ => String::new(),
+        };
+        let running = self.running.iter().fold(column![].spacing(10), |c, x| {
+            let stop_button = match x.is_stopping() {
+                true => button(text("stopping...").center()),
+                false => {
+                    button(text("stop game").center()).on_press(Message::StopGame(x.id.clone()))
+                }
+            };
+            c.push(
+                row![
+                    text(format!(
+                        "{} {} {}",
+                        x.game_name, x.release.channel_name, x.release.version
+                    ))
+                    .width(250),
+                    stop_button,
+                ]
+                .spacing(10)
+                .align_y(Center),
+            )
+        });
+        view_utils::centered_container(
+            column![
+                text(format!("Playing {}", name)).size(32),
+                vertical_space().height(30),
+                running,
+                vertical_space().height(30),
+                button(text("library").center()).on_press(Message::GoToScreen(Screen::Main)),
+            ]
+            .align_x(Center)
+            .into(),
+        )
+    }
+
+    /// Ways out of a failed launch, shown on the error screen
+    pub(crate) fn failed_launch_actions(&self) -> Option<Row<'_, Message>> {
+        let (game, release) = self.failed_launch.as_ref()?;
+        Some(
+            row![
+                button(text("verify").center())
+                    .on_press(Message::VerifyRelease(game.clone(), release.clone())),
+                button(text("reinstall").center())
+                    .on_press(Message::ReinstallRelease(game.clone(), release.clone())),
+            ]
+            .spacing(10),
+        )
+    }
+
+    pub(crate) fn clear_failed_launch(&mut self) {
+        self.failed_launch = None;
+    }
+
+    pub(crate) fn subscription(&self) -> Subscription<Message> {
+        Subscription::batch(self.running.iter().map(RunningGame::subscription))
+    }
+
+    /// Checks the installed files of a release, and marks it as not installed if the
+    /// executable is gone so it can be downloaded again.
+    fn verify_release(game: &Game, release: &Release, blackboard: &mut Blackboard) {
+        let executable_path = utils::get_exe_path(
+            &blackboard.config.get_games_dir(),
+            &game.name_id,
+            &release.channel_name,
+            &release.version,
+        )
+        .join(&release.executable_path);
+        blackboard.status_message = match prepare_executable(&executable_path) {
+            Ok(_) => Some(format!("{} {} looks fine", game.name, release.version)),
+            Err(e) => {
+                if let Err(e) = blackboard.config.update_install_state(
+                    &game.name_id,
+                    &release.version,
+                    &release.channel_name,
+                    ReleaseState::NotInstalled,
+                ) {
+                    error!("failed to update install state: {}", e);
+                }
+                blackboard.update_selected_game();
+                Some(format!("{}, reinstall the release", e))
+            }
+        };
+    }
+}
+
+impl MessageHandler for PlayingMessageHandler {
+    fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
+        match message {
+            Message::Run(game, release) => {
+                let id = RunningGame::build_id(&game, &release);
+                if self.launching.contains(&id) {
+                    info!("{} is already being launched", id);
+                    blackboard.status_message =
+                        Some(format!("{} {} is starting", game.name, release.version));
+                    return Task::none();
+                }
+                if self.running.iter().any(|x| x.id == id) {
+                    info!("{} is already running", id);
+                    blackboard.status_message = Some(format!(
+                        "{} {} is already running",
+                        game.name, release.version
+                    ));
+                    blackboard.screen = Screen::PlayingGame(game.name.to_string());
+                    return Task::none();
+                }
+                self.launching.push(id);
+                return Task::done(Message::SyncSaves(SyncPoint::BeforeLaunch, game, release));
+            }
+            Message::Launch(game, release) => {
+                let id = RunningGame::build_id(&game, &release);
+                self.launching.retain(|x| *x != id);
+                let running_game = RunningGame::new(
+                    &game,
+                    &release,
+                    &blackboard.config.get_games_dir(),
+                    blackboard.config.launch_prefix.as_deref(),
+                );
+                blackboard.screen = Screen::PlayingGame(game.name.to_string());
+                self.running.push(running_game);
+            }
+            Message::GameEvent((id, event)) => {
+                let Some(index) = self.running.iter().position(|x| x.id == id) else {
+                    error!("got event for unknown game {}", id);
+                    return Task::none();
+                };
+                let mut crash = None;
+                let status = match event {
+                    GameEvent::Started => {
+                        self.running[index].started_at = Utc::now();
+                        return Task::none();
+                    }
+                    GameEvent::Exited(exit) => {
+                        let running_game = &self.running[index];
+                        let duration = Utc::now() - running_game.started_at;
+                        blackboard.config.add_session(
+                            &running_game.game_name_id,
+                            PlaySession {
+                                started_at: running_game.started_at,
+                                duration_secs: duration.num_seconds().max(0) as u64,
+                                version: running_game.release.version.to_string(),
+                                channel_name: running_game.release.channel_name.to_string(),
+                                exit_code: exit.code,
+                                log_file: running_game
+                                    .log_path
+                                    .exists()
+                                    .then(|| running_game.log_path.clone()),
+                            },
+                        );
+                        blackboard.update_selected_game();
+                        // being stopped from the client ends with a signal, that's no crash
+                        if exit.is_crash() && !running_game.is_stopping() {
+                            crash = Some(Crash {
+                                game_name: running_game.game_name.to_string(),
+                                game_name_id: running_game.game_name_id.to_string(),
+                                version: running_game.release.version.to_string(),
+                                channel_name: running_game.release.channel_name.to_string(),
+                                crashed_at: Utc::now(),
+                                log_file: running_game
+                                    .log_path
+                                    .exists()
+                                    .then(|| running_game.log_path.clone()),
+                                exit: exit.clone(),
+                            });
+                        }
+                        format!("{} {}", running_game.game_name, exit)
+                    }
+                    GameEvent::Failed(e) => {
+                        let running_game = self.running.remove(index);
+                        error!("failed to launch {}: {}", running_game.game_name_id, e);
+                        blackboard.screen = Screen::Error(e.to_string());
+                        self.failed_launch = Some((running_game.game, running_game.release));
+                        return Task::none();
+                    }
+                };
+                let running_game = self.running.remove(index);
+                info!("{}: {}", running_game.game_name_id, status);
+                blackboard.status_message = Some(status);
+                if let Screen::PlayingGame(_) = blackboard.screen {
+                    blackboard.screen = match self.running.last() {
+                        None => Screen::Main,
+                        Some(other) => Screen::PlayingGame(other.game_name.to_string()),
+                    };
+                }
+                return Task::done(Message::SyncSaves(
+                    SyncPoint::AfterSession(crash),
+                    running_game.game,
+                    running_game.release,
+                ));
+            }
+            Message::StopGame(id) => {
+                if let Some(running_game) = self.running.iter().find(|x| x.id == id) {
+                    info!("stopping {}", id);
+                    running_game.stop_requested.store(true, Ordering::Relaxed);
+                }
+            }
+            Message::VerifyRelease(game, release) => {
+                self.failed_launch = None;
+                Self::verify_release(&game, &release, blackboard);
+                blackboard.screen = Screen::Main;
+            }
+            Message::ReinstallRelease(game, release) => {
+                self.failed_launch = None;
+                blackboard.screen = Screen::Main;
+                if blackboard.release_busy(&game.name_id, &release.channel_name, &release.version) {
+                    blackboard.status_message = Some(format!(
+                        "{} {} is running or being downloaded",
+                        game.name, release.version
+                    ));
+                    return Task::none();
+                }
+                let request = DownloadRequest::build(&release, &game, &blackboard.config);
+                return Task::perform(
+                    remove_release_dirs(
+                        blackboard.config.get_games_dir(),
+                        game.name_id.to_string(),
+                        vec![release],
+                    ),
+                    |x| x,
+                )
+                .then(move |result| {
+                    // a release that is still partly there can't be downloaded again
+                    let download = match result.error {
+                        None => Task::done(Message::Download(request.clone())),
+                        Some(_) => Task::none(),
+                    };
+                    Task::done(Message::ReleasesUninstalled(result)).chain(download)
+                });
+            }
+            _ => error!("invalid playing state message: {:?}", message),
+        }
+        Task::none()
+    }
+}
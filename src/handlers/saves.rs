@@ -0,0 +1,480 @@
+use crate::api;
+use crate::blackboard::Blackboard;
+use crate::client_config::{get_config_dir, Game, Release, SaveMeta};
+use crate::handlers::crash::Crash;
+use crate::handlers::MessageHandler;
+use crate::messages::Message;
+use crate::{utils, view_utils, Screen};
+use chrono::{DateTime, Local, Utc};
+use iced::widget::{button, column, row, text, vertical_space};
+use iced::{Center, Element, Task};
+use log::{error, info};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Local saves are backed up before being replaced by the ones from the server
+const MAX_SAVE_BACKUPS: usize = 5;
+
+fn get_save_backups_dir(game_name_id: &str) -> PathBuf {
+    get_config_dir().join("save_backups").join(game_name_id)
+}
+
+#[derive(Debug, Clone)]
+pub enum SyncPoint {
+    BeforeLaunch,
+    /// A crash of the session is shown once the saves are synced, so it doesn't compete
+    /// with a save conflict for the screen
+    AfterSession(Option<Crash>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ConflictChoice {
+    KeepLocal,
+    UseServer,
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyncPlan {
+    InSync(Option<SaveMeta>),
+    Upload,
+    Download(SaveMeta),
+    Conflict { local: SaveMeta, remote: SaveMeta },
+}
+
+/// Decides which side to sync from. The digest of the last sync tells which side changed
+/// since, when both did the user has to choose.
+fn plan(local: Option<SaveMeta>, remote: Option<SaveMeta>, synced: Option<&SaveMeta>) -> SyncPlan {
+    match (local, remote) {
+        (None, None) => SyncPlan::InSync(None),
+        (Some(_), None) => SyncPlan::Upload,
+        (None, Some(remote)) => SyncPlan::Download(remote),
+        (Some(local), Some(remote)) if local.sha256 == remote.sha256 => {
+            SyncPlan::InSync(Some(remote))
+        }
+        (Some(local), Some(remote)) => {
+            let local_changed = synced.is_none_or(|x| x.sha256 != local.sha256);
+            let remote_changed = synced.is_none_or(|x| x.sha256 != remote.sha256);
+            match (local_changed, remote_changed) {
+                (true, false) => SyncPlan::Upload,
+                (false, true) => SyncPlan::Download(remote),
+                _ => SyncPlan::Conflict { local, remote },
+            }
+        }
+    }
+}
+
+/// Everything needed to sync the saves of a game, passed along the async steps
+#[derive(Debug, Clone)]
+pub struct SaveSync {
+    point: SyncPoint,
+    game: Game,
+    release: Release,
+    drops_url: String,
+    session_token: String,
+    save_paths: Vec<PathBuf>,
+}
+
+impl SaveSync {
+    fn build(point: SyncPoint, game: Game, release: Release, blackboard: &Blackboard) -> Self {
+        // the game may be stale, the last sync is recorded in the config
+        let game = blackboard
+            .config
+            .get_account_games()
+            .into_iter()
+            .find(|x| x.name_id == game.name_id)
+            .unwrap_or(game);
+        let release_dir = utils::get_exe_path(
+            &blackboard.config.get_games_dir(),
+            &game.name_id,
+            &release.channel_name,
+            &release.version,
+        );
+        let save_paths = game
+            .save_paths
+            .iter()
+            .map(|x| release_dir.join(shellexpand::tilde(x).as_ref()))
+            .collect();
+        SaveSync {
+            point,
+            game,
+            release,
+            drops_url: blackboard.config.get_drops_url(),
+            session_token: blackboard.config.get_session_token().to_string(),
+            save_paths,
+        }
+    }
+}
+
+/// Save files as archive entries, named `{save path index}/{path in directory}` and
+/// sorted so the digest doesn't depend on the order of the file system.
+fn collect_save_files(save_paths: &[PathBuf]) -> io::Result<Vec<(String, PathBuf)>> {
+    fn visit(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+            if file_type.is_dir() {
+                visit(&entry.path(), &name, files)?;
+            } else if file_type.is_file() {
+                files.push((name, entry.path()));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    for (i, path) in save_paths.iter().enumerate() {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => visit(path, &i.to_string(), &mut files)?,
+            Ok(metadata) if metadata.is_file() => files.push((i.to_string(), path.clone())),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    files.sort_by(|x, y| x.0.cmp(&y.0));
+    Ok(files)
+}
+
+/// Reads the local saves into a zip archive, `None` if there are no save files
+fn snapshot_saves(save_paths: &[PathBuf]) -> io::Result<Option<(SaveMeta, Vec<u8>)>> {
+    let files = collect_save_files(save_paths)?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let mut hasher = Sha256::new();
+    let mut modified_at = DateTime::<Utc>::UNIX_EPOCH;
+    let mut writer = ZipWriter::new(Cursor::new(vec![]));
+    for (name, path) in files {
+        let content = fs::read(&path)?;
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+        modified_at = modified_at.max(fs::metadata(&path)?.modified()?.into());
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .map_err(io::Error::other)?;
+        writer.write_all(&content)?;
+    }
+    let archive = writer.finish().map_err(io::Error::other)?.into_inner();
+    let meta = SaveMeta {
+        sha256: format!("{:x}", hasher.finalize()),
+        modified_at,
+    };
+    Ok(Some((meta, archive)))
+}
+
+fn backup_saves(game_name_id: &str, archive: &[u8]) -> io::Result<()> {
+    let dir = get_save_backups_dir(game_name_id);
+    fs::create_dir_all(&dir)?;
+    let mut backups: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(Result::ok)
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|x| x == "zip"))
+        .collect();
+    // names are timestamps, so they sort oldest first
+    backups.sort();
+    for old_backup in backups
+        .iter()
+        .take((backups.len() + 1).saturating_sub(MAX_SAVE_BACKUPS))
+    {
+        fs::remove_file(old_backup)?;
+    }
+    let path = dir.join(format!("{}.zip", Utc::now().format("%Y%m%d-%H%M%S")));
+    info!("backing up saves to {}", path.display());
+    fs::write(path, archive)
+}
+
+/// Replaces the local saves with the ones from the server. Local save files are removed
+/// first, left over they would make the saves differ from the server's right away.
+fn restore_saves(archive: &[u8], save_paths: &[PathBuf]) -> io::Result<()> {
+    let mut archive = ZipArchive::new(Cursor::new(archive)).map_err(io::Error::other)?;
+    for (_, path) in collect_save_files(save_paths)? {
+        fs::remove_file(path)?;
+    }
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(io::Error::other)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let Some(name) = entry.enclosed_name() else {
+            error!("skipping save entry with unsafe path {}", entry.name());
+            continue;
+        };
+        let mut components = name.components();
+        let save_path = components
+            .next()
+            .and_then(|x| x.as_os_str().to_str()?.parse::<usize>().ok())
+            .and_then(|x| save_paths.get(x));
+        let Some(save_path) = save_path else {
+            error!("skipping save entry {} without a save path", name.display());
+            continue;
+        };
+        let rest = components.as_path();
+        let target = match rest.as_os_str().is_empty() {
+            true => save_path.to_path_buf(),
+            false => save_path.join(rest),
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&target)?)?;
+    }
+    Ok(())
+}
+
+async fn plan_sync(sync: SaveSync) -> (SaveSync, Result<SyncPlan, String>) {
+    let result = async {
+        let save_paths = sync.save_paths.clone();
+        let local = tokio::task::spawn_blocking(move || snapshot_saves(&save_paths))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?
+            .map(|(meta, _)| meta);
+        let remote =
+            api::fetch_save_meta(&sync.drops_url, &sync.session_token, &sync.game.name_id).await?;
+        Ok(plan(local, remote, sync.game.synced_saves.as_ref()))
+    }
+    .await;
+    (sync, result)
+}
+
+async fn upload(sync: SaveSync) -> (SaveSync, Result<Option<SaveMeta>, String>) {
+    let result = async {
+        let save_paths = sync.save_paths.clone();
+        let snapshot = tokio::task::spawn_blocking(move || snapshot_saves(&save_paths))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        let Some((meta, archive)) = snapshot else {
+            return Ok(None);
+        };
+        api::upload_saves(
+            &sync.drops_url,
+            &sync.session_token,
+            &sync.game.name_id,
+            &meta,
+            archive,
+        )
+        .await?;
+        Ok(Some(meta))
+    }
+    .await;
+    (sync, result)
+}
+
+async fn download(
+    sync: SaveSync,
+    remote: SaveMeta,
+) -> (SaveSync, Result<Option<SaveMeta>, String>) {
+    let result = async {
+        let archive =
+            api::fetch_saves(&sync.drops_url, &sync.session_token, &sync.game.name_id).await?;
+        let save_paths = sync.save_paths.clone();
+        let game_name_id = sync.game.name_id.to_string();
+        let restored = tokio::task::spawn_blocking(move || {
+            if let Some((_, local)) = snapshot_saves(&save_paths)? {
+                backup_saves(&game_name_id, &local)?;
+            }
+            restore_saves(&archive, &save_paths)?;
+            // the saves as they are on disk now, so the next sync sees them unchanged
+            snapshot_saves(&save_paths).map(|x| x.map(|(meta, _)| meta))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+        if restored.as_ref().is_some_and(|x| x.sha256 != remote.sha256) {
+            error!("restored saves differ from the server's");
+        }
+        Ok(restored)
+    }
+    .await;
+    (sync, result)
+}
+
+struct SaveConflict {
+    sync: SaveSync,
+    local: SaveMeta,
+    remote: SaveMeta,
+}
+
+#[derive(Default)]
+pub struct SavesMessageHandler {
+    conflict: Option<SaveConflict>,
+}
+
+impl SavesMessageHandler {
+    pub fn view(&self, _: &Blackboard) -> Element<'_, Message> {
+        let Some(conflict) = &self.conflict else {
+            return column![].into();
+        };
+        let format_time = |x: DateTime<Utc>| {
+            DateTime::<Local>::from(x)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        };
+        let newer = match conflict.local.modified_at > conflict.remote.modified_at {
+            true => "this machine has the newer saves",
+            false => "the server has the newer saves",
+        };
+        view_utils::container_with_title(
+            "Save conflict".to_string(),
+            column![
+                text(format!(
+                    "The saves of {} changed here and on the server since the last sync",
+                    conflict.sync.game.name
+                ))
+                .width(400),
+                text(format!(
+                    "this machine: {}",
+                    format_time(conflict.local.modified_at)
+                )),
+                text(format!(
+                    "server: {}",
+                    format_time(conflict.remote.modified_at)
+                )),
+                text(newer).size(12),
+                vertical_space().height(20),
+                row![
+                    button(text("keep this machine's").center())
+                        .on_press(Message::ResolveSaveConflict(ConflictChoice::KeepLocal)),
+                    button(text("use the server's").center())
+                        .on_press(Message::ResolveSaveConflict(ConflictChoice::UseServer)),
+                    button(text("decide later").center())
+                        .on_press(Message::ResolveSaveConflict(ConflictChoice::Skip)),
+                ]
+                .spacing(10),
+            ]
+            .spacing(5)
+            .align_x(Center),
+        )
+    }
+
+    /// Launching continues once the saves are synced, whether that worked or not
+    fn finish(sync: SaveSync) -> Task<Message> {
+        match sync.point {
+            SyncPoint::BeforeLaunch => Task::done(Message::Launch(sync.game, sync.release)),
+            SyncPoint::AfterSession(Some(crash)) => Task::done(Message::GameCrashed(crash)),
+            SyncPoint::AfterSession(None) => Task::none(),
+        }
+    }
+}
+
+impl MessageHandler for SavesMessageHandler {
+    fn update(&mut self, message: Message, blackboard: &mut Blackboard) -> Task<Message> {
+        match message {
+            Message::SyncSaves(point, game, release) => {
+                let sync = SaveSync::build(point, game, release, blackboard);
+                if sync.save_paths.is_empty() || !blackboard.config.has_session_token() {
+                    return Self::finish(sync);
+                }
+                blackboard.status_message = Some(format!("syncing saves of {}", sync.game.name));
+                return Task::perform(plan_sync(sync), Message::SaveSyncPlanned);
+            }
+            Message::SaveSyncPlanned((sync, Ok(plan))) => match plan {
+                SyncPlan::InSync(meta) => {
+                    if meta != sync.game.synced_saves {
+                        blackboard.config.set_synced_saves(&sync.game.name_id, meta);
+                        blackboard.update_selected_game();
+                    }
+                    blackboard.status_message = Some("saves are up to date".to_string());
+                    return Self::finish(sync);
+                }
+                SyncPlan::Upload => return Task::perform(upload(sync), Message::SavesSynced),
+                SyncPlan::Download(remote) => {
+                    return Task::perform(download(sync, remote), Message::SavesSynced)
+                }
+                SyncPlan::Conflict { local, remote } => {
+                    info!("save conflict for {}", sync.game.name_id);
+                    self.conflict = Some(SaveConflict {
+                        sync,
+                        local,
+                        remote,
+                    });
+                    blackboard.screen = Screen::SaveConflict;
+                }
+            },
+            Message::SaveSyncPlanned((sync, Err(e))) => {
+                error!("failed to check saves of {}: {}", sync.game.name_id, e);
+                blackboard.status_message = Some(format!("failed to sync saves: {}", e));
+                return Self::finish(sync);
+            }
+            Message::ResolveSaveConflict(choice) => {
+                let Some(conflict) = self.conflict.take() else {
+                    return Task::none();
+                };
+                blackboard.screen = Screen::Main;
+                return match choice {
+                    ConflictChoice::KeepLocal => {
+                        Task::perform(upload(conflict.sync), Message::SavesSynced)
+                    }
+                    ConflictChoice::UseServer => Task::perform(
+                        download(conflict.sync, conflict.remote),
+                        Message::SavesSynced,
+                    ),
+                    ConflictChoice::Skip => Self::finish(conflict.sync),
+                };
+            }
+            Message::SavesSynced((sync, Ok(meta))) => {
+                info!("synced saves of {}", sync.game.name_id);
+                blackboard.config.set_synced_saves(&sync.game.name_id, meta);
+                blackboard.update_selected_game();
+                blackboard.status_message = Some(format!("synced saves of {}", sync.game.name));
+                return Self::finish(sync);
+            }
+            Message::SavesSynced((sync, Err(e))) => {
+                error!("failed to sync saves of {}: {}", sync.game.name_id, e);
+                blackboard.status_message = Some(format!("failed to sync saves: {}", e));
+                return Self::finish(sync);
+            }
+            _ => error!("invalid saves state message: {:?}", message),
+        }
+        Task::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn restored_server_saves_stay_in_sync() {
+        let server = tempfile::tempdir().unwrap();
+        let server_paths = vec![server.path().join("saves")];
+        write(&server_paths[0].join("slot1.sav"), "server");
+        let (remote, archive) = snapshot_saves(&server_paths).unwrap().unwrap();
+
+        let local = tempfile::tempdir().unwrap();
+        let local_paths = vec![local.path().join("saves")];
+        write(&local_paths[0].join("slot1.sav"), "local");
+        write(&local_paths[0].join("old/slot2.sav"), "local only");
+        let (local_meta, _) = snapshot_saves(&local_paths).unwrap().unwrap();
+        assert!(matches!(
+            plan(Some(local_meta), Some(remote.clone()), None),
+            SyncPlan::Conflict { .. }
+        ));
+
+        // the user picks the saves of the server
+        restore_saves(&archive, &local_paths).unwrap();
+        assert!(!local_paths[0].join("old/slot2.sav").exists());
+        let (restored, _) = snapshot_saves(&local_paths).unwrap().unwrap();
+        assert_eq!(restored.sha256, remote.sha256);
+        assert!(matches!(
+            plan(Some(restored.clone()), Some(remote), Some(&restored)),
+            SyncPlan::InSync(_)
+        ));
+    }
+}
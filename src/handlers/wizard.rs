@@ -26,10 +26,10 @@ impl WizardMessageHandler {
         self.games_dir_input = "".to_string();
         self.drops_url_input = "".to_string();
     }
-    pub fn view(&self, blackboard: &Blackboard) -> Element<Message> {
+    pub fn view(&self, blackboard: &Blackboard) -> Element<'_, Message> {
         view_utils::container_with_title("Welcome".to_string(), self.wizard_column(blackboard))
     }
-    fn wizard_column(&self, blackboard: &Blackboard) -> Column<Message> {
+    fn wizard_column(&self, blackboard: &Blackboard) -> Column<'_, Message> {
         let host_input = text_input("drops server url", &self.drops_url_input)
             .width(200)
             .on_input(Message::DropsUrlChanged)
@@ -56,6 +56,22 @@ impl WizardMessageHandler {
             .spacing(20)
             .align_y(Center);
 
+        // several accounts can share a server, each gets its own session and games
+        let known_accounts: Vec<String> = blackboard
+            .config
+            .accounts
+            .iter()
+            .filter(|x| !self.drops_url_input.is_empty() && x.url == self.drops_url_input)
+            .map(|x| x.display_name())
+            .collect();
+        let known_accounts_text = (!known_accounts.is_empty()).then(|| {
+            text(format!(
+                "adds another account next to {}",
+                known_accounts.join(", ")
+            ))
+            .size(12)
+        });
+
         let dir_select_input = text_input("select games dir", &self.games_dir_input)
             .width(200)
             .padding(10)
@@ -76,7 +92,14 @@ impl WizardMessageHandler {
             .spacing(20)
             .align_y(Center);
 
-        let should_show = match !(!self.has_valid_games_dir || !self.has_valid_host) {
+        // a new account becomes the active one
+        let busy = blackboard.active_account_busy();
+        let busy_text = busy.then(|| {
+            text("stop running games and downloads before adding an account")
+                .size(12)
+                .color(Color::from_rgb(0.8, 0.4, 0.4))
+        });
+        let should_show = match self.has_valid_games_dir && self.has_valid_host && !busy {
             true => Some(true),
             false => None,
         };
@@ -101,17 +124,16 @@ impl WizardMessageHandler {
             .spacing(30)
             .align_y(Center);
 
-        column![
-            host_err_text,
-            test_host_row,
-            vertical_space().height(10),
-            text(ok_text).color(Color::from_rgb(0.4, 0.7, 0.4)),
-            select_file_row,
-            vertical_space().height(80),
-            bottom_bar
-        ]
-        .width(500)
-        .spacing(0)
+        column![host_err_text, test_host_row]
+            .push_maybe(known_accounts_text)
+            .push(vertical_space().height(10))
+            .push(text(ok_text).color(Color::from_rgb(0.4, 0.7, 0.4)))
+            .push(select_file_row)
+            .push_maybe(busy_text)
+            .push(vertical_space().height(80))
+            .push(bottom_bar)
+            .width(500)
+            .spacing(0)
     }
 }
 
@@ -138,9 +160,11 @@ impl WizardMessageHandler {
                 self.drops_url_input = s;
                 self.has_valid_host = false;
             }
+            Message::FinishWizard if blackboard.active_account_busy() => {}
             Message::FinishWizard => {
                 let account = DropsAccountConfig {
                     id: Uuid::new_v4(),
+                    name: None,
                     url: self.drops_url_input.to_string(),
                     games_dir: self.games_dir_input.to_string(),
                     username: "".to_string(),
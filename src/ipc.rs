@@ -88,7 +88,7 @@ fn handle_other_clients_opening() -> impl Stream<Item = Event> {
             ipmb::join::<String, String>(options, None).expect("failed to setup ipc server");
         let mut receiver = receiver;
         loop {
-            if let Err(_) = output.send(Event::Yield).await {
+            if output.send(Event::Yield).await.is_err() {
                 debug!("failed to send field!");
             }
 
@@ -122,7 +122,7 @@ pub(crate) fn try_send_args() -> Result<(), Error> {
     }
 
     // let's send this argument to the running instance
-    if let Some(arg) = args.get(0) {
+    if let Some(arg) = args.first() {
         let options = ipmb::Options::new("drops-client", label!("client"), "");
         let (sender, _) =
             ipmb::join::<String, String>(options, None).expect("failed to setup ipc server");
@@ -2,6 +2,7 @@
 mod api;
 mod blackboard;
 mod client_config;
+mod config_migrations;
 mod errors;
 mod handlers;
 mod ipc;
@@ -10,18 +11,25 @@ mod tasks;
 mod utils;
 mod view_utils;
 
-use crate::client_config::{ClientConfig, Game, Release, ReleaseState};
+use crate::client_config::{ClientConfig, ConfigBackup, Game, Release, ReleaseState};
 use crate::errors::{ConfigError, FetchGamesError, LoginError};
+use crate::handlers::accounts::AccountsMessageHandler;
 use crate::handlers::client_update::ClientUpdateHandler;
+use crate::handlers::crash::CrashMessageHandler;
 use crate::handlers::download::{DownloadMessageHandler, DownloadRequest};
 use crate::handlers::games::GamesMessageHandler;
+use crate::handlers::library::{LibraryMessageHandler, MoveProgress};
 use crate::handlers::login::LoginMessageHandler;
+use crate::handlers::logs::LogsMessageHandler;
+use crate::handlers::playing::PlayingMessageHandler;
+use crate::handlers::saves::SavesMessageHandler;
 use crate::handlers::wizard::WizardMessageHandler;
 use crate::handlers::MessageHandler;
 use crate::ipc::{Event, LockFileWithDrop};
 use crate::messages::Message;
 use anyhow::anyhow;
-use blackboard::Blackboard;
+use blackboard::{Activity, Blackboard};
+use chrono::{DateTime, Local};
 use env_logger::Env;
 use iced::widget::{button, column, row, text, vertical_space};
 use iced::{window, Center, Element, Size, Task};
@@ -30,6 +38,8 @@ use log::{error, info};
 use secrecy::SecretString;
 use std::default::Default;
 use std::env;
+use std::io;
+use std::path::PathBuf;
 use sysinfo::System;
 
 #[derive(Default)]
@@ -37,8 +47,14 @@ struct DropsClient {
     blackboard: Blackboard,
     downloading: DownloadMessageHandler,
     gaming: GamesMessageHandler,
+    playing: PlayingMessageHandler,
+    crashes: CrashMessageHandler,
+    saves: SavesMessageHandler,
+    logs: LogsMessageHandler,
     wizard: WizardMessageHandler,
     login: LoginMessageHandler,
+    accounts: AccountsMessageHandler,
+    library: LibraryMessageHandler,
     requested_game_to_play: Option<String>,
     run_from_args_issue: RunFromArgsIssue,
     client_updating: ClientUpdateHandler,
@@ -52,13 +68,28 @@ pub enum Screen {
     ClientUpdateAvailable(self_update::update::Release),
     Login,
     LoggingIn,
-    Downloading,
+    Downloads,
+    Logs,
     Main,
     Error(String),
     PlayingGame(String),
+    CrashReport,
+    SaveConflict,
+    ConfigRecovery(ConfigRecovery),
+    Accounts,
+    Library,
+}
+
+/// Why the config couldn't be loaded and the ways out, shown instead of discarding it
+#[derive(Clone, Debug)]
+pub struct ConfigRecovery {
+    error: String,
+    broken_file: Option<PathBuf>,
+    backup: Option<ConfigBackup>,
 }
 
 #[derive(Default)]
+#[allow(clippy::large_enum_variant)]
 enum RunFromArgsIssue {
     #[default]
     NotSet,
@@ -93,23 +124,48 @@ impl DropsClient {
     }
 
     pub fn subscription(&self) -> Subscription<Message> {
-        Subscription::batch([self.downloading.subscription()])
+        Subscription::batch([
+            self.downloading.subscription(),
+            self.playing.subscription(),
+            self.crashes.subscription(),
+            self.library.subscription(),
+        ])
     }
 
     fn have_valid_config(&self) -> bool {
         self.blackboard.have_valid_config()
     }
 
-    pub fn view(&self) -> Element<Message> {
+    /// Games and downloads write to the account they were started for, which is always
+    /// the active one
+    fn activity(&self) -> Activity {
+        Activity {
+            playing: (self.playing.running.iter().map(|x| x.id.to_string()))
+                .chain(self.playing.launching.iter().cloned())
+                .collect(),
+            downloading: self.downloading.pending_ids(),
+            moving_library: self.active_library_moving(),
+        }
+    }
+
+    /// Releases of the active account are on their way to another games dir
+    fn active_library_moving(&self) -> bool {
+        self.library.moving_account() == Some(self.blackboard.config.active_account)
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
         match &self.blackboard.screen {
             Screen::Empty => column![].into(),
             Screen::Wizard => self.wizard.view(&self.blackboard),
             Screen::Login | Screen::LoggingIn => self.login.view(&self.blackboard),
-            Screen::Downloading => self.downloading.view(&self.blackboard),
+            Screen::Downloads => self.downloading.view(&self.blackboard),
             Screen::ClientUpdateAvailable(_) => self.client_updating.view(&self.blackboard),
-            Screen::PlayingGame(name) => {
-                view_utils::centered_container(text(format!("Playing {}", name)).into())
-            }
+            Screen::PlayingGame(_) => self.playing.view(&self.blackboard),
+            Screen::Logs => self.logs.view(&self.blackboard),
+            Screen::CrashReport => self.crashes.view(&self.blackboard),
+            Screen::SaveConflict => self.saves.view(&self.blackboard),
+            Screen::Accounts => self.accounts.view(&self.blackboard),
+            Screen::Library => self.library.view(&self.blackboard),
             Screen::Main => {
                 match &self.run_from_args_issue {
                     RunFromArgsIssue::Error(_) | RunFromArgsIssue::FoundUpdate(..) => {
@@ -117,21 +173,55 @@ impl DropsClient {
                     }
                     _ => {}
                 }
-                self.gaming.view(&self.blackboard)
+                self.gaming.view(&self.blackboard, &self.downloading)
             }
+            Screen::ConfigRecovery(recovery) => view_utils::container_with_title(
+                "Config could not be loaded".to_string(),
+                column![text(&recovery.error).width(500)]
+                    .push_maybe(recovery.broken_file.as_ref().map(|x| {
+                        text(format!("The file was moved to {}", x.display()))
+                            .size(12)
+                            .width(500)
+                    }))
+                    .push_maybe(recovery.backup.as_ref().map(|x| {
+                        text(format!(
+                            "Found a backup from {}",
+                            DateTime::<Local>::from(x.modified).format("%Y-%m-%d %H:%M")
+                        ))
+                    }))
+                    .push(vertical_space().height(20))
+                    .push(
+                        row![]
+                            .push_maybe(recovery.backup.as_ref().map(|x| {
+                                button(text("restore backup"))
+                                    .on_press(Message::RestoreConfig(x.path.clone()))
+                            }))
+                            .push(
+                                button(text("open file location"))
+                                    .on_press(Message::OpenConfigLocation),
+                            )
+                            .push(button(text("start fresh")).on_press(Message::StartFresh))
+                            .spacing(10),
+                    )
+                    .spacing(5),
+            ),
             Screen::Error(message) => view_utils::container_with_title(
                 "Error".to_string(),
                 column![]
                     .push(vertical_space())
                     .push(text(message).size(28).width(300))
                     .push(vertical_space().height(20))
-                    .push(button(text("close")).on_press(Message::CloseError))
+                    .push(
+                        row![button(text("close")).on_press(Message::CloseError)]
+                            .push_maybe(self.playing.failed_launch_actions())
+                            .spacing(10),
+                    )
                     .push(vertical_space()),
             ),
         }
     }
 
-    fn display_run_from_args_issue(&self) -> Element<Message> {
+    fn display_run_from_args_issue(&self) -> Element<'_, Message> {
         match &self.run_from_args_issue {
             RunFromArgsIssue::Error(message) => view_utils::centered_container(
                 column![]
@@ -150,8 +240,10 @@ impl DropsClient {
                                 DownloadRequest::build(new_release, game, &self.blackboard.config),
                             )))
                             .push(
-                                button(text("play"))
-                                    .on_press(Message::Run(installed_release.clone())),
+                                button(text("play")).on_press(Message::Run(
+                                    game.clone(),
+                                    installed_release.clone(),
+                                )),
                             )
                             .spacing(10),
                     ),
@@ -162,7 +254,11 @@ impl DropsClient {
         }
     }
 
-    fn try_run_from_args(&mut self) {
+    fn try_run_from_args(&mut self) -> Task<Message> {
+        // the game is started once the library has moved
+        if self.active_library_moving() {
+            return Task::none();
+        }
         self.run_from_args_issue = self.handle_args_game_running();
         if let RunFromArgsIssue::CanPlay(release) = &self.run_from_args_issue {
             let game_name_id = self.requested_game_to_play.as_ref().unwrap();
@@ -170,10 +266,12 @@ impl DropsClient {
             let game = games.iter().find(|x| &x.name_id == game_name_id).unwrap();
             let set_version = release.version.to_string();
             self.blackboard.selected_version = Some(set_version);
-            self.blackboard.run_release(game, &release);
+            let task = Task::done(Message::Run(game.clone(), release.clone()));
             self.run_from_args_issue = RunFromArgsIssue::NotSet;
             self.requested_game_to_play = None;
+            return task;
         }
+        Task::none()
     }
     fn handle_args_game_running(&mut self) -> RunFromArgsIssue {
         let Some(game_name_id) = &self.requested_game_to_play else {
@@ -234,15 +332,25 @@ impl DropsClient {
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
+        let task = self.handle_message(message);
+        // handlers check what runs through the blackboard
+        self.blackboard.activity = self.activity();
+        task
+    }
+
+    fn handle_message(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::Ipc(event) => match event {
                 Event::ArgsReceived(args) => {
                     self.requested_game_to_play = Some(args);
-                    self.try_run_from_args();
+                    return self.try_run_from_args();
                 }
                 Event::Yield => {}
             },
-            Message::CloseError => self.blackboard.screen = Screen::Main,
+            Message::CloseError => {
+                self.playing.clear_failed_launch();
+                self.blackboard.screen = Screen::Main;
+            }
             Message::UpdateClient(_) => {
                 return self.client_updating.update(message, &mut self.blackboard)
             }
@@ -251,37 +359,178 @@ impl DropsClient {
                 self.blackboard.set_initial_screen();
                 self.requested_game_to_play = None;
             }
-            Message::GoToScreen(screen) => self.blackboard.screen = screen,
+            Message::GoToScreen(screen) => {
+                self.blackboard.screen = screen;
+            }
+
+            Message::Run(..)
+            | Message::Download(..)
+            | Message::RetryDownload(_)
+            | Message::ResumeDownload(_)
+                if self.active_library_moving() =>
+            {
+                self.blackboard.status_message =
+                    Some("wait for the library to finish moving".to_string());
+            }
 
             // Games
-            Message::Run(_) | Message::SelectGame(_) => {
+            Message::Run(..)
+            | Message::Launch(..)
+            | Message::GameEvent(_)
+            | Message::StopGame(_)
+            | Message::VerifyRelease(..)
+            | Message::ReinstallRelease(..) => {
+                return self.playing.update(message, &mut self.blackboard)
+            }
+            Message::SelectGame(_)
+            | Message::Uninstall(_)
+            | Message::ConfirmUninstall
+            | Message::CancelUninstall
+            | Message::ReleasesUninstalled(_)
+            | Message::TogglePinned(_)
+            | Message::SelectGameTab(_)
+            | Message::LaunchArgsChanged(_)
+            | Message::LaunchEnvChanged(_)
+            | Message::LaunchWorkingDirChanged(_)
+            | Message::LaunchPrefixChanged(_)
+            | Message::SavePathsChanged(_)
+            | Message::CarryOverChanged(_)
+            | Message::DefaultLaunchPrefixChanged(_)
+            | Message::SaveGameSettings
+            | Message::KeepInstalledChanged(_) => {
                 return self.gaming.update(message, &mut self.blackboard)
             }
 
             // Downloading
-            Message::CloseDownloadError(_)
-            | Message::DownloadProgressing(_)
-            | Message::Download(..) => {
+            Message::RemoveDownload(_)
+            | Message::RetryDownload(_)
+            | Message::PauseDownload(_)
+            | Message::ResumeDownload(_)
+            | Message::CancelDownload(_)
+            | Message::ClearFinishedDownloads
+            | Message::MaxParallelDownloadsChanged(_)
+            | Message::CarriedOver(_)
+            | Message::PartialDownloadsFound(_)
+            | Message::DownloadProgressing(_) => {
                 return self.downloading.update(message, &mut self.blackboard)
             }
+            Message::Download(..) => {
+                // Updating from the args prompt continues in the library
+                if let RunFromArgsIssue::FoundUpdate(..) = self.run_from_args_issue {
+                    self.run_from_args_issue = RunFromArgsIssue::NotSet;
+                    self.requested_game_to_play = None;
+                }
+                return self.downloading.update(message, &mut self.blackboard);
+            }
 
             // Wizard
             Message::WizardCanReachHostChecked(_)
             | Message::FinishWizard
             | Message::TestDropsUrl
             | Message::DropsUrlChanged(_)
-            | Message::SelectGamesDir => return self.wizard.update(message, &mut self.blackboard),
+            | Message::SelectGamesDir => {
+                return self.wizard.update(message, &mut self.blackboard);
+            }
 
             // Login
             Message::Login
-            | Message::ServerChanged(_)
+            | Message::AccountChanged(_)
             | Message::LoggedInFinished(_)
             | Message::UsernameChanged(_)
             | Message::PasswordChanged(_) => {
-                return self.login.update(message, &mut self.blackboard);
+                return self.login.update(message, &mut self.blackboard)
+            }
+            // Accounts
+            Message::ShowAccounts
+            | Message::EditAccount(_)
+            | Message::CancelAccountEdit
+            | Message::AccountNameChanged(_)
+            | Message::AccountUrlChanged(_)
+            | Message::TestAccountUrl
+            | Message::AccountUrlChecked(_)
+            | Message::SelectAccountGamesDir
+            | Message::MoveGamesToggled(_)
+            | Message::SaveAccount
+            | Message::DeleteAccount(_)
+            | Message::ConfirmDeleteAccount => {
+                let active_account = self.blackboard.config.active_account;
+                let task = self.accounts.update(message, &mut self.blackboard);
+                // removing the active account switches to another one
+                if self.blackboard.config.active_account != active_account {
+                    let screen = self.blackboard.screen.clone();
+                    self.logout();
+                    self.blackboard.screen = screen;
+                    if self.have_valid_config() {
+                        let username = self.blackboard.config.get_username();
+                        self.login.set_username(&username);
+                    }
+                }
+                return task;
             }
+            // Library
+            Message::MoveLibrary(_)
+            | Message::LibraryMoveProgressed(_)
+            | Message::OldLibraryRemoved(_)
+            | Message::ScanLibrary
+            | Message::LibraryScanned(_)
+            | Message::RemoveUnknownDir(_)
+            | Message::ConfirmRemoveUnknownDir
+            | Message::CancelRemoveUnknownDir
+            | Message::UnknownDirRemoved(_)
+            | Message::SideloadRelease(_) => {
+                let was_moving = self.active_library_moving();
+                if let Message::LibraryMoveProgressed(Ok(MoveProgress::Moved(moved))) = &message {
+                    let (from, to) = moved.games_dirs();
+                    self.downloading.move_library(from, to);
+                }
+                let task = self.library.update(message, &mut self.blackboard);
+                if was_moving && !self.active_library_moving() {
+                    return Task::batch([task, self.try_run_from_args()]);
+                }
+                return task;
+            }
+            // Logs
+            Message::ShowLogs(_)
+            | Message::SelectLog(_)
+            | Message::LogLoaded(_)
+            | Message::LogSearchChanged(_)
+            | Message::CopyLog => return self.logs.update(message, &mut self.blackboard),
+            // Saves
+            Message::SyncSaves(..)
+            | Message::SaveSyncPlanned(_)
+            | Message::SavesSynced(_)
+            | Message::ResolveSaveConflict(_) => {
+                return self.saves.update(message, &mut self.blackboard)
+            }
+            // Crash reports
+            Message::GameCrashed(_)
+            | Message::SubmitCrashReport
+            | Message::DismissCrash
+            | Message::CrashReportQueued(_)
+            | Message::SendCrashReports
+            | Message::CrashReportsSent(_) => {
+                return self.crashes.update(message, &mut self.blackboard)
+            }
+
             Message::Logout => self.logout(),
             Message::ConfigOpened(result) => return self.handle_config_open(result),
+            Message::RestoreConfig(path) => {
+                return Task::perform(ClientConfig::restore_backup(path), Message::ConfigRestored)
+            }
+            Message::ConfigRestored(Ok(config)) => return self.use_config(config),
+            Message::ConfigRestored(Err(e)) => {
+                error!("failed to restore config backup: {}", e);
+                if let Screen::ConfigRecovery(recovery) = &mut self.blackboard.screen {
+                    recovery.error = format!("failed to restore backup: {}", e);
+                    recovery.backup = None;
+                }
+            }
+            Message::OpenConfigLocation => {
+                if let Err(e) = utils::open_in_file_manager(&client_config::get_config_dir()) {
+                    error!("failed to open config location: {}", e);
+                }
+            }
+            Message::StartFresh => return self.use_config(ClientConfig::default()),
 
             Message::FetchGames => {
                 return tasks::perform_fetch_games_from_config(&self.blackboard.config)
@@ -306,14 +555,21 @@ impl DropsClient {
                         self.blackboard.config.clear_session_token();
                     }
                 }
-                error!("failed to fetch games! {:?}", e)
+                error!("failed to fetch games! {:?}", e);
+                // the games known from the config can still be resumed
+                return DownloadMessageHandler::scan_partial_downloads(&self.blackboard.config);
             }
             Message::GamesFetched(Ok(games_response)) => {
                 self.blackboard
                     .config
                     .sync_and_save(games_response)
                     .expect("Failed to receive games response");
-                self.try_run_from_args();
+                // the server is reachable again, so it's a good time to send queued reports
+                return Task::batch([
+                    self.try_run_from_args(),
+                    Task::done(Message::SendCrashReports),
+                    DownloadMessageHandler::scan_partial_downloads(&self.blackboard.config),
+                ]);
             }
 
             Message::SelectedChannelChanged(channel_name) => {
@@ -322,7 +578,7 @@ impl DropsClient {
                     Some(&channel_name),
                     None,
                 );
-                self.blackboard.selected_version = newest.and_then(|x| Some(x.version));
+                self.blackboard.selected_version = newest.map(|x| x.version);
                 self.blackboard.selected_channel = Some(channel_name);
             }
             Message::SelectedVersionChanged(version) => {
@@ -334,18 +590,38 @@ impl DropsClient {
     }
 
     fn handle_config_open(&mut self, result: Result<ClientConfig, ConfigError>) -> Task<Message> {
-        self.blackboard.config = result.unwrap_or_else(|e| {
-            let error_message = match e {
-                ConfigError::DialogClosed => "Dialog closed".to_string(),
-                ConfigError::IoError(e) => format!("io error: {}", e).to_string(),
-            };
-            info!("failed to open config, recreating {}", error_message);
-            ClientConfig {
-                active_account: Default::default(),
-                accounts: vec![],
-                is_active: false,
-            }
-        });
+        let config = match result {
+            Ok(config) => config,
+            Err(e) => {
+                error!("failed to open config: {}", e);
+                let backup = client_config::newest_valid_backup();
+                let first_start = matches!(e, ConfigError::IoError(io::ErrorKind::NotFound));
+                if first_start && backup.is_none() {
+                    info!("found no config, starting from scratch");
+                    return self.use_config(ClientConfig::default());
+                }
+                // saving a new config would overwrite a file that might still be fixable
+                let broken_file = match e {
+                    ConfigError::Parse { .. } | ConfigError::Migration(_) => {
+                        client_config::move_broken_config()
+                            .inspect_err(|e| error!("failed to move broken config: {}", e))
+                            .ok()
+                    }
+                    ConfigError::IoError(_) => None,
+                };
+                self.blackboard.screen = Screen::ConfigRecovery(ConfigRecovery {
+                    error: e.to_string(),
+                    broken_file,
+                    backup,
+                });
+                return Task::none();
+            }
+        };
+        self.use_config(config)
+    }
+
+    fn use_config(&mut self, config: ClientConfig) -> Task<Message> {
+        self.blackboard.config = config;
         if self.have_valid_config() {
             let username_in_config = self.blackboard.config.get_username();
             self.login.set_username(&username_in_config);
@@ -366,7 +642,6 @@ impl DropsClient {
         self.blackboard.selected_game = None;
         self.blackboard.selected_channel = None;
         self.wizard.clear_input();
-        self.blackboard.is_playing = false;
 
         self.login.password_input = SecretString::new("".into());
         self.login.username_input.clear();
@@ -389,7 +664,7 @@ fn main() -> Result<(), anyhow::Error> {
                 if args.len() > 1 {
                     return Err(anyhow!("invalid number of arguments!"));
                 }
-                if let Some(game_name_id) = args.get(0) {
+                if let Some(game_name_id) = args.first() {
                     info!("new client was started with game_name_id arg: {}, killing old client process",game_name_id);
                     p.kill();
                 }
@@ -1,20 +1,58 @@
-use crate::client_config::{ClientConfig, Game, Release, SessionToken};
+use crate::api::InstalledRelease;
+use crate::client_config::{ClientConfig, Game, Release, SaveMeta, SessionToken};
 use crate::errors::{ConfigError, FetchGamesError, LoginError};
+use crate::handlers::crash::{Crash, QueuedCrashReport};
 use crate::handlers::download::{DownloadError, DownloadProgress, DownloadRequest};
+use crate::handlers::games::{GameTab, KeepInstalled, UninstallResult, UninstallTarget};
+use crate::handlers::library::{LibraryMove, LibraryScan, MoveProgress};
+use crate::handlers::login::AccountOption;
+use crate::handlers::playing::GameEvent;
+use crate::handlers::saves::{ConflictChoice, SaveSync, SyncPlan, SyncPoint};
 use crate::ipc::Event;
 use crate::Screen;
 use drops_messages::requests::GetGamesResponse;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use uuid::Uuid;
 
 #[derive(Debug, Clone)]
 pub enum Message {
     ConfigOpened(Result<ClientConfig, ConfigError>),
+    RestoreConfig(PathBuf),
+    ConfigRestored(Result<ClientConfig, ConfigError>),
+    OpenConfigLocation,
+    StartFresh,
     Login,
     LoggedInFinished(Result<SessionToken, LoginError>),
     FetchGames,
     GamesFetched(Result<GetGamesResponse, FetchGamesError>),
 
     SelectGame(Game),
-    Run(Release),
+    Run(Game, Release),
+    Launch(Game, Release),
+    SyncSaves(SyncPoint, Game, Release),
+    SaveSyncPlanned((SaveSync, Result<SyncPlan, String>)),
+    SavesSynced((SaveSync, Result<Option<SaveMeta>, String>)),
+    ResolveSaveConflict(ConflictChoice),
+    GameEvent((String, GameEvent)),
+    StopGame(String),
+    VerifyRelease(Game, Release),
+    ReinstallRelease(Game, Release),
+    Uninstall(UninstallTarget),
+    ConfirmUninstall,
+    CancelUninstall,
+    ReleasesUninstalled(UninstallResult),
+    TogglePinned(Release),
+    SelectGameTab(GameTab),
+    LaunchArgsChanged(String),
+    LaunchEnvChanged(String),
+    LaunchWorkingDirChanged(String),
+    LaunchPrefixChanged(String),
+    SavePathsChanged(String),
+    CarryOverChanged(String),
+    DefaultLaunchPrefixChanged(String),
+    SaveGameSettings,
+    KeepInstalledChanged(KeepInstalled),
     Download(DownloadRequest),
 
     UsernameChanged(String),
@@ -24,7 +62,29 @@ pub enum Message {
 
     WizardCanReachHostChecked(Result<(), String>),
     SelectedChannelChanged(String),
-    ServerChanged(String),
+    AccountChanged(AccountOption),
+    ShowAccounts,
+    EditAccount(Uuid),
+    CancelAccountEdit,
+    AccountNameChanged(String),
+    AccountUrlChanged(String),
+    TestAccountUrl,
+    AccountUrlChecked(Result<(), String>),
+    SelectAccountGamesDir,
+    MoveGamesToggled(bool),
+    SaveAccount,
+    MoveLibrary(LibraryMove),
+    LibraryMoveProgressed(Result<MoveProgress, String>),
+    OldLibraryRemoved(Result<(), String>),
+    ScanLibrary,
+    LibraryScanned(Result<LibraryScan, String>),
+    RemoveUnknownDir(PathBuf),
+    ConfirmRemoveUnknownDir,
+    CancelRemoveUnknownDir,
+    UnknownDirRemoved(Result<PathBuf, String>),
+    SideloadRelease(PathBuf),
+    DeleteAccount(Uuid),
+    ConfirmDeleteAccount,
     SelectGamesDir,
     FinishWizard,
 
@@ -32,11 +92,30 @@ pub enum Message {
     GoToInitialScreen,
     UpdateClient(self_update::update::Release),
     DownloadProgressing((String, Result<DownloadProgress, DownloadError>)),
-    CloseDownloadError(String),
+    CarriedOver((InstalledRelease, Result<usize, String>)),
+    RemoveDownload(String),
+    RetryDownload(String),
+    PauseDownload(String),
+    ResumeDownload(String),
+    CancelDownload(String),
+    PartialDownloadsFound(HashSet<String>),
+    ClearFinishedDownloads,
+    MaxParallelDownloadsChanged(usize),
     Logout,
     ClearRequestedGameToPlay,
     SelectedVersionChanged(String),
     CloseError,
     Ipc(Event),
     CloseClient,
+    ShowLogs(Option<PathBuf>),
+    SelectLog(PathBuf),
+    LogLoaded(Result<String, String>),
+    LogSearchChanged(String),
+    CopyLog,
+    GameCrashed(Crash),
+    SubmitCrashReport,
+    DismissCrash,
+    CrashReportQueued(Result<QueuedCrashReport, String>),
+    SendCrashReports,
+    CrashReportsSent(Vec<Uuid>),
 }
@@ -1,5 +1,5 @@
 use crate::api::{fetch_games, login};
-use crate::client_config::{ClientConfig};
+use crate::client_config::ClientConfig;
 use crate::messages::Message;
 use iced::Task;
 
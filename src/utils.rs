@@ -3,7 +3,17 @@ use anyhow::anyhow;
 use log::info;
 use self_update::backends::github;
 use self_update::{cargo_crate_version, version};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub fn get_game_path(games_dir: &str, game_name_id: &str) -> PathBuf {
+    PathBuf::new()
+        .join("drops")
+        .join(games_dir)
+        .join(game_name_id)
+}
 
 pub fn get_exe_path(
     games_dir: &str,
@@ -11,14 +21,65 @@ pub fn get_exe_path(
     channel_name: &str,
     version: &str,
 ) -> PathBuf {
-    PathBuf::new()
-        .join("drops")
-        .join(games_dir)
-        .join(game_name_id)
+    get_game_path(games_dir, game_name_id)
         .join(channel_name)
         .join(version)
 }
 
+/// Suffix of the file a release archive is downloaded to, next to the release dir
+pub const PARTIAL_DOWNLOAD_SUFFIX: &str = ".part";
+/// Suffix of the dir a release is extracted into, next to the release dir
+pub const EXTRACT_SUFFIX: &str = ".extracting";
+
+/// Whether a name in a channel dir was left by an interrupted download or install
+pub fn is_install_leftover(name: &str) -> bool {
+    name.ends_with(PARTIAL_DOWNLOAD_SUFFIX) || name.ends_with(EXTRACT_SUFFIX)
+}
+
+/// Identifies a release of a game across downloads and running games
+pub fn release_id(game_name_id: &str, channel_name: &str, version: &str) -> String {
+    format!("{}/{}/{}", game_name_id, channel_name, version)
+}
+
+/// Dir the partial downloads and extractions of a game are kept in
+pub fn get_download_dir(games_dir: &str, game_name_id: &str) -> PathBuf {
+    PathBuf::new().join(games_dir).join(game_name_id)
+}
+
+pub fn get_partial_download_path(
+    games_dir: &str,
+    game_name_id: &str,
+    channel_name: &str,
+    version: &str,
+) -> PathBuf {
+    get_download_dir(games_dir, game_name_id)
+        .join(channel_name)
+        .join(format!("{}{}", version, PARTIAL_DOWNLOAD_SUFFIX))
+}
+
+/// Dir a release is extracted into before it replaces the install dir of the version
+pub fn get_extract_path(
+    games_dir: &str,
+    game_name_id: &str,
+    channel_name: &str,
+    version: &str,
+) -> PathBuf {
+    get_download_dir(games_dir, game_name_id)
+        .join(channel_name)
+        .join(format!("{}{}", version, EXTRACT_SUFFIX))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect())
+}
+
 pub fn newest_release_by_state(
     releases: &[Release],
     channel: Option<&str>,
@@ -26,12 +87,182 @@ pub fn newest_release_by_state(
 ) -> Option<Release> {
     releases
         .iter()
-        .filter(|x| channel.map_or(true, |c| x.channel_name == c))
-        .filter(|x| state.as_ref().map_or(true, |s| &x.state == s))
+        .filter(|x| channel.is_none_or(|c| x.channel_name == c))
+        .filter(|x| state.as_ref().is_none_or(|s| &x.state == s))
         .max_by(|x, y| x.release_date.cmp(&y.release_date))
         .cloned()
 }
 
+/// Splits a command line into arguments. Whitespace separates arguments unless it is
+/// quoted with ' or ", and \ escapes the next character outside of single quotes.
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.get_or_insert_with(String::new).push(c),
+            (_, '\\') => match chars.next() {
+                Some(escaped) => current.get_or_insert_with(String::new).push(escaped),
+                None => return Err("trailing \\ in arguments".to_string()),
+            },
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("unclosed {} in arguments", q));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Inverse of [`split_args`], quotes arguments only when needed
+pub fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|x| {
+            match x.is_empty() || x.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+                true => format!("'{}'", x.replace('\'', "'\\''")),
+                false => x.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Matches a `/` separated path against a glob pattern. `*` and `?` match within a path
+/// component, `**` matches any number of components.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn match_component(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                match_component(&pattern[1..], name)
+                    || (!name.is_empty() && match_component(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => match_component(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) if p == n => match_component(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+    fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+        match (pattern.first(), path.first()) {
+            (None, None) => true,
+            (Some(&"**"), _) => {
+                match_components(&pattern[1..], path)
+                    || (!path.is_empty() && match_components(pattern, &path[1..]))
+            }
+            (Some(p), Some(x)) => {
+                let p: Vec<char> = p.chars().collect();
+                let x: Vec<char> = x.chars().collect();
+                match_component(&p, &x) && match_components(&pattern[1..], &path[1..])
+            }
+            _ => false,
+        }
+    }
+    let pattern: Vec<&str> = pattern.split('/').filter(|x| !x.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+    match_components(&pattern, &path)
+}
+
+pub const EXE_PLACEHOLDER: &str = "{exe}";
+pub const GAME_DIR_PLACEHOLDER: &str = "{game_dir}";
+
+/// Checks a launch prefix template such as `gamemoderun {exe}` when it is saved.
+/// Only the [`EXE_PLACEHOLDER`] and [`GAME_DIR_PLACEHOLDER`] placeholders are known.
+pub fn validate_launch_prefix(template: &str) -> Result<(), String> {
+    let words = split_args(template)?;
+    let Some(program) = words.first() else {
+        return Err("launch prefix is empty".to_string());
+    };
+    if program == GAME_DIR_PLACEHOLDER {
+        return Err(format!(
+            "launch prefix can't start with {}",
+            GAME_DIR_PLACEHOLDER
+        ));
+    }
+    for word in &words {
+        let mut rest = word
+            .replace(EXE_PLACEHOLDER, "")
+            .replace(GAME_DIR_PLACEHOLDER, "");
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 1..start + end];
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(format!(
+                    "unknown placeholder {{{}}}, use {} or {}",
+                    name, EXE_PLACEHOLDER, GAME_DIR_PLACEHOLDER
+                ));
+            }
+            rest = rest[start + end + 1..].to_string();
+        }
+    }
+    Ok(())
+}
+
+/// Expands a launch prefix into the full command line of a game, the executable is
+/// appended when the template doesn't place it itself.
+pub fn expand_launch_prefix(
+    template: &str,
+    executable_path: &Path,
+    game_dir: &Path,
+) -> Result<Vec<String>, String> {
+    // the wrapper runs in the working dir of the game, so relative paths would break
+    let absolute = |x: &Path| {
+        std::path::absolute(x)
+            .unwrap_or(x.to_path_buf())
+            .to_string_lossy()
+            .to_string()
+    };
+    let executable_path = absolute(executable_path);
+    let game_dir = absolute(game_dir);
+    let words = split_args(template)?;
+    let has_exe = words.iter().any(|x| x.contains(EXE_PLACEHOLDER));
+    let mut command: Vec<String> = words
+        .iter()
+        .map(|x| {
+            x.replace(EXE_PLACEHOLDER, &executable_path)
+                .replace(GAME_DIR_PLACEHOLDER, &game_dir)
+        })
+        .collect();
+    if !has_exe {
+        command.push(executable_path);
+    }
+    Ok(command)
+}
+
+/// Opens a directory in the file manager of the platform
+pub fn open_in_file_manager(path: &Path) -> io::Result<()> {
+    let program = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(program).arg(path).spawn()?;
+    Ok(())
+}
+
+pub fn format_duration(secs: u64) -> String {
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+    match hours {
+        0 if minutes == 0 => format!("{}s", secs),
+        0 => format!("{}m", minutes),
+        _ => format!("{}h {}m", hours, minutes),
+    }
+}
+
 pub fn default_platform() -> &'static str {
     if cfg!(windows) {
         return "windows";
@@ -59,7 +290,7 @@ pub fn look_for_newer_version() -> Result<Option<self_update::update::Release>,
     }
 
     // Assume first one is latest
-    let newer = releases.into_iter().nth(0).unwrap();
+    let newer = releases.into_iter().next().unwrap();
     let newer_version = newer.version.to_string();
 
     let current = cargo_crate_version!();
@@ -70,3 +301,139 @@ pub fn look_for_newer_version() -> Result<Option<self_update::update::Release>,
 
     Ok(Some(newer))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(x: &[&str]) -> Vec<String> {
+        x.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(
+            split_args("  -w  --fps 60 "),
+            Ok(args(&["-w", "--fps", "60"]))
+        );
+        assert_eq!(split_args(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn splits_quoted_and_escaped_args() {
+        assert_eq!(
+            split_args(r#"--name "Player One" 'it''s' a\ b"#),
+            Ok(args(&["--name", "Player One", "its", "a b"]))
+        );
+        assert_eq!(split_args(r#""say \"hi\"""#), Ok(args(&[r#"say "hi""#])));
+        // no escapes inside single quotes
+        assert_eq!(split_args(r"'C:\games'"), Ok(args(&[r"C:\games"])));
+        assert_eq!(split_args(r#""" ''"#), Ok(args(&["", ""])));
+    }
+
+    #[test]
+    fn rejects_unbalanced_quotes() {
+        assert!(split_args(r#"--name "Player"#).is_err());
+        assert!(split_args("it's").is_err());
+        assert!(split_args(r"trailing\").is_err());
+    }
+
+    #[test]
+    fn join_round_trips_through_split() {
+        let cases = [
+            args(&["-w", "--fps", "60"]),
+            args(&["--name", "Player One"]),
+            args(&["it's", r#"say "hi""#, r"C:\games", ""]),
+            args(&["tab\there", "new\nline"]),
+        ];
+        for case in cases {
+            assert_eq!(split_args(&join_args(&case)), Ok(case));
+        }
+        assert_eq!(join_args(&args(&["-w", "a b"])), "-w 'a b'");
+    }
+
+    #[test]
+    fn validates_launch_prefix_placeholders() {
+        assert_eq!(validate_launch_prefix("gamemoderun"), Ok(()));
+        assert_eq!(
+            validate_launch_prefix("prime-run {exe} --fullscreen"),
+            Ok(())
+        );
+        assert_eq!(validate_launch_prefix("run --dir={game_dir} {exe}"), Ok(()));
+        // braces that are no placeholder pass through
+        assert_eq!(validate_launch_prefix("sh -c '{ {exe}; }'"), Ok(()));
+        assert!(validate_launch_prefix("").is_err());
+        assert!(validate_launch_prefix("{game_dir} {exe}").is_err());
+        assert!(validate_launch_prefix("wrap %command%").is_ok());
+        assert!(validate_launch_prefix("wrap {command}").is_err());
+        assert!(validate_launch_prefix("wrap 'unclosed").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn expands_launch_prefix() {
+        let exe = Path::new("/games/space/beta/1.0/space");
+        let dir = Path::new("/games/space/beta/1.0");
+        assert_eq!(
+            expand_launch_prefix("prime-run {exe} --fullscreen", exe, dir),
+            Ok(args(&[
+                "prime-run",
+                "/games/space/beta/1.0/space",
+                "--fullscreen"
+            ]))
+        );
+        assert_eq!(
+            expand_launch_prefix("'my wrapper' --root={game_dir}", exe, dir),
+            Ok(args(&[
+                "my wrapper",
+                "--root=/games/space/beta/1.0",
+                "/games/space/beta/1.0/space"
+            ]))
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn appends_executable_without_placeholder() {
+        let exe = Path::new("/games/space/beta/1.0/space");
+        let dir = Path::new("/games/space/beta/1.0");
+        // %command% is no placeholder here, the executable still goes last
+        assert_eq!(
+            expand_launch_prefix("gamemoderun %command%", exe, dir),
+            Ok(args(&[
+                "gamemoderun",
+                "%command%",
+                "/games/space/beta/1.0/space"
+            ]))
+        );
+        assert!(expand_launch_prefix("wrap \"{exe}", exe, dir).is_err());
+    }
+
+    #[test]
+    fn glob_wildcards_stay_in_one_component() {
+        assert!(glob_match("saves/*.sav", "saves/slot1.sav"));
+        assert!(!glob_match("saves/*.sav", "saves/old/slot1.sav"));
+        assert!(glob_match("slot?.sav", "slot1.sav"));
+        assert!(!glob_match("slot?.sav", "slot10.sav"));
+        assert!(!glob_match("slot?.sav", "slot.sav"));
+        assert!(glob_match("*", "config.ini"));
+        assert!(!glob_match("*", "saves/config.ini"));
+    }
+
+    #[test]
+    fn glob_double_star_matches_any_depth() {
+        assert!(glob_match("**/*.cfg", "game.cfg"));
+        assert!(glob_match("**/*.cfg", "a/b/c/game.cfg"));
+        assert!(glob_match("saves/**", "saves/a/b"));
+        assert!(glob_match("saves/**", "saves"));
+        assert!(glob_match("mods/**/data", "mods/x/y/data"));
+        assert!(!glob_match("mods/**/data", "mods/x/y/data/z"));
+        assert!(!glob_match("saves/**", "config/saves"));
+    }
+
+    #[test]
+    fn glob_ignores_redundant_slashes() {
+        assert!(glob_match("/saves//*.sav", "saves/slot1.sav"));
+        assert!(glob_match("saves/", "saves"));
+    }
+}
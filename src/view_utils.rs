@@ -1,5 +1,6 @@
 use crate::blackboard::Blackboard;
 use crate::messages::Message;
+use crate::Screen;
 use iced::widget::{
     button, column, container, horizontal_space, row, text, vertical_space, Column, Container,
 };
@@ -38,16 +39,30 @@ pub fn container_with_title(title: String, content: Column<Message>) -> Element<
 pub fn container_with_top_bar_and_side_view<'a>(
     content: Container<'a, Message>,
     blackboard: &Blackboard,
+    pending_downloads: usize,
 ) -> Element<'a, Message> {
     let config = &blackboard.config;
+    let account_name = config
+        .get_active_account()
+        .map(|x| x.display_name())
+        .unwrap_or_default();
+    let status = blackboard
+        .status_message
+        .as_ref()
+        .map(|x| text(x.to_string()).size(12));
     let header = container(
         row![
-            text(format!("Logged in as  {}", config.get_username())),
+            column![text(format!("Logged in as  {}", account_name))].push_maybe(status),
             horizontal_space(),
             column!["drops", cargo_crate_version!()],
             horizontal_space(),
+            button(text(format!("downloads ({})", pending_downloads)).center())
+                .on_press(Message::GoToScreen(Screen::Downloads)),
+            button(text("logs").center()).on_press(Message::ShowLogs(None)),
+            button(text("accounts").center()).on_press(Message::ShowAccounts),
             button(text("logout").center()).on_press(Message::Logout)
         ]
+        .spacing(10)
         .padding(10)
         .align_y(Center),
     );